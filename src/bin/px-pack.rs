@@ -0,0 +1,111 @@
+//! Packs numbered frames ("prefix_0001.png", "prefix_0002.png", ...) into sprite sheets
+//! named "prefix_CxR_WxH.png" and writes a "<name>.spriteset.ron" manifest for them.
+//!
+//! Usage: px-pack <frames dir> <out dir> [--name <set name>] [--no-trim]
+
+use std::path::PathBuf;
+use std::process::ExitCode;
+use px_test::pack::{group_frames, load_frames, pack_group, set_bounds, sheet_manifest, write_manifest};
+
+const USAGE: &str = "usage: px-pack <frames dir> <out dir> [--name <set name>] [--no-trim]";
+
+fn main() -> ExitCode {
+    let mut paths = Vec::new();
+    let mut name = None;
+    let mut trim = true;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--name" => name = args.next(),
+            "--no-trim" => trim = false,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return ExitCode::SUCCESS;
+            }
+            _ => paths.push(PathBuf::from(arg)),
+        }
+    }
+
+    let [in_dir, out_dir] = paths.as_slice() else {
+        eprintln!("{}", USAGE);
+        return ExitCode::FAILURE;
+    };
+    // default the set name to the output folder, matching assets/textures/<set>
+    let Some(name) = name.or_else(|| out_dir.file_name()?.to_str().map(String::from)) else {
+        eprintln!("could not pick a set name from {}, pass --name", out_dir.display());
+        return ExitCode::FAILURE;
+    };
+
+    if let Err(e) = std::fs::create_dir_all(out_dir) {
+        eprintln!("{}: {}", out_dir.display(), e);
+        return ExitCode::FAILURE;
+    }
+
+    let groups = match group_frames(in_dir) {
+        Ok(groups) => groups,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut failed = false;
+    let mut loaded = Vec::new();
+    for (prefix, frame_paths) in &groups {
+        match load_frames(prefix, frame_paths) {
+            Ok(frames) => loaded.push((prefix.clone(), frames)),
+            Err(e) => {
+                eprintln!("{}", e);
+                failed = true;
+            }
+        }
+    }
+
+    if loaded.is_empty() {
+        return ExitCode::FAILURE;
+    }
+
+    // every sheet is cut to the same area so they line up and share a frame size
+    let trimmed = match set_bounds(&name, &loaded, trim) {
+        Ok(trimmed) => trimmed,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut sheets = Vec::new();
+    for (prefix, frames) in &loaded {
+        match pack_group(prefix, frames, trimmed, out_dir) {
+            Ok(sheet) => {
+                println!("Saved sprite sheet: {}", sheet.path.display());
+                sheets.push(sheet);
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                failed = true;
+            }
+        }
+    }
+
+    for sheet in &sheets {
+        if sheet_manifest(sheet).is_none() {
+            eprintln!("{}: prefix is not <direction>_<state>, left out of the manifest", sheet.prefix);
+        }
+    }
+
+    match write_manifest(out_dir, &name, &sheets) {
+        Ok(path) => println!("Saved sprite set manifest: {}", path.display()),
+        Err(e) => {
+            eprintln!("{}", e);
+            failed = true;
+        }
+    }
+
+    if failed {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
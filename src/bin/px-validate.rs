@@ -0,0 +1,47 @@
+//! Checks sprite sets for missing sheets, misnamed files, grids that don't fit their images
+//! and stray files, printing a report per set. Folders with only an Aseprite export
+//! are checked through their "<set>.aseprite.json".
+//!
+//! Usage: px-validate [<set dir>...], with no folders every set under assets/textures is checked.
+
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use px_test::validate::{validate_all, validate_set};
+
+const USAGE: &str = "usage: px-validate [<set dir>...]";
+
+fn main() -> ExitCode {
+    let mut dirs = Vec::new();
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return ExitCode::SUCCESS;
+            }
+            _ => dirs.push(PathBuf::from(arg)),
+        }
+    }
+
+    let reports = if dirs.is_empty() {
+        let textures = Path::new("assets/textures");
+        match validate_all(textures) {
+            Ok(reports) => reports,
+            Err(e) => {
+                eprintln!("{}: {}", textures.display(), e);
+                return ExitCode::FAILURE;
+            }
+        }
+    } else {
+        dirs.iter().map(|dir| validate_set(dir)).collect()
+    };
+
+    for report in &reports {
+        println!("{}", report);
+    }
+
+    if reports.iter().any(|report| report.has_errors()) {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
@@ -1,7 +1,9 @@
+use std::f32::consts::TAU;
 use bevy::prelude::*;
-use enum_iterator::Sequence;
+use enum_iterator::{Sequence, all};
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Component, Sequence)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Component, Sequence, Serialize, Deserialize)]
 pub enum Direction8 {
     North,
     Northeast,
@@ -27,6 +29,7 @@ impl Direction8 {
         }
     }
 
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(s: &str) -> Option<Direction8> {
         match s {
             "north" => Some(Direction8::North),
@@ -41,7 +44,39 @@ impl Direction8 {
         }
     }
 
-    pub fn to_translation(&self) -> Vec2 {
+    /// The facing at the same angle in a 16 direction circle.
+    pub fn to_direction16(self) -> Direction16 {
+        match self {
+            Direction8::North => Direction16::North,
+            Direction8::Northeast => Direction16::Northeast,
+            Direction8::East => Direction16::East,
+            Direction8::Southeast => Direction16::Southeast,
+            Direction8::South => Direction16::South,
+            Direction8::Southwest => Direction16::Southwest,
+            Direction8::West => Direction16::West,
+            Direction8::Northwest => Direction16::Northwest,
+        }
+    }
+
+    /// The 45 degree sector `vector` points into, `None` for a zero vector.
+    pub fn from_vector(vector: Vec2) -> Option<Direction8> {
+        if vector == Vec2::ZERO {
+            return None;
+        }
+
+        // clockwise from north, like the variants
+        let angle = vector.x.atan2(vector.y).rem_euclid(TAU);
+        let sector = (angle / (TAU / 8.0)).round() as usize % 8;
+        all::<Direction8>().nth(sector)
+    }
+
+    /// Up, down, left and right buttons that together point this way.
+    pub fn to_dpad(self) -> [bool; 4] {
+        let v = self.to_translation();
+        [v.y > 0.0, v.y < 0.0, v.x < 0.0, v.x > 0.0]
+    }
+
+    pub fn to_translation(self) -> Vec2 {
         match self {
             Direction8::North => Vec2::new(0.0, 1.0),
             Direction8::Northeast => Vec2::new(1.0, 1.0).normalize(),
@@ -53,4 +88,134 @@ impl Direction8 {
             Direction8::Northwest => Vec2::new(-1.0, 1.0).normalize(),
         }
     }
-}
\ No newline at end of file
+}
+
+/// Sprite facings at 22.5 degree steps, clockwise from north.
+/// 4 and 8 direction sprite sets use every fourth or every second facing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Sequence, Serialize, Deserialize)]
+pub enum Direction16 {
+    North,
+    NorthNortheast,
+    Northeast,
+    EastNortheast,
+    East,
+    EastSoutheast,
+    Southeast,
+    SouthSoutheast,
+    South,
+    SouthSouthwest,
+    Southwest,
+    WestSouthwest,
+    West,
+    WestNorthwest,
+    Northwest,
+    NorthNorthwest,
+}
+
+impl Direction16 {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Direction16::North => "north",
+            Direction16::NorthNortheast => "northnortheast",
+            Direction16::Northeast => "northeast",
+            Direction16::EastNortheast => "eastnortheast",
+            Direction16::East => "east",
+            Direction16::EastSoutheast => "eastsoutheast",
+            Direction16::Southeast => "southeast",
+            Direction16::SouthSoutheast => "southsoutheast",
+            Direction16::South => "south",
+            Direction16::SouthSouthwest => "southsouthwest",
+            Direction16::Southwest => "southwest",
+            Direction16::WestSouthwest => "westsouthwest",
+            Direction16::West => "west",
+            Direction16::WestNorthwest => "westnorthwest",
+            Direction16::Northwest => "northwest",
+            Direction16::NorthNorthwest => "northnorthwest",
+        }
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(s: &str) -> Option<Direction16> {
+        all::<Direction16>().find(|direction| direction.as_str() == s)
+    }
+
+    /// Position on the circle, 0 is north and each step is 22.5 degrees clockwise.
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    pub fn from_index(index: usize) -> Direction16 {
+        all::<Direction16>().nth(index % 16).unwrap()
+    }
+
+    /// The facing flipped horizontally, north and south map onto themselves.
+    pub fn mirrored(self) -> Direction16 {
+        Direction16::from_index(16 - self.index())
+    }
+
+    pub fn to_translation(self) -> Vec2 {
+        let angle = self.index() as f32 * TAU / 16.0;
+        Vec2::new(angle.sin(), angle.cos())
+    }
+}
+
+/// How many facings a sprite set is drawn with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum DirectionResolution {
+    Four,
+    #[default]
+    Eight,
+    Sixteen,
+}
+
+impl DirectionResolution {
+    pub fn count(self) -> usize {
+        match self {
+            DirectionResolution::Four => 4,
+            DirectionResolution::Eight => 8,
+            DirectionResolution::Sixteen => 16,
+        }
+    }
+
+    /// The smallest resolution that has all of the given facings.
+    pub fn smallest_containing(directions: impl Iterator<Item = Direction16> + Clone) -> DirectionResolution {
+        [DirectionResolution::Four, DirectionResolution::Eight]
+            .into_iter()
+            .find(|resolution| directions.clone().all(|direction| resolution.contains(direction)))
+            .unwrap_or(DirectionResolution::Sixteen)
+    }
+
+    /// Direction16 steps between neighbouring facings.
+    fn step(self) -> usize {
+        16 / self.count()
+    }
+
+    /// The facings a set with this resolution provides sheets for.
+    pub fn facings(self) -> impl Iterator<Item = Direction16> {
+        all::<Direction16>().step_by(self.step())
+    }
+
+    pub fn contains(self, direction: Direction16) -> bool {
+        direction.index().is_multiple_of(self.step())
+    }
+
+    /// The available facing closest to a movement vector. On a tie, such as a diagonal
+    /// in a 4 direction set, `current` is kept so the sprite doesn't flicker between facings.
+    pub fn nearest(self, vector: Vec2, current: Option<Direction16>) -> Direction16 {
+        let count = self.count() as f32;
+        // clockwise from north in units of one facing, matching Direction16
+        let position = vector.x.atan2(vector.y).rem_euclid(TAU) / TAU * count;
+
+        if let Some(current) = current
+            && self.contains(current)
+        {
+            let distance = (position - (current.index() / self.step()) as f32).rem_euclid(count);
+            if distance.min(count - distance) <= 0.5 + 1e-4 {
+                return current;
+            }
+        }
+
+        let facing = position.round() as usize % self.count();
+        Direction16::from_index(facing * self.step())
+    }
+}
@@ -0,0 +1,213 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+use crate::game::character_input::CharacterInput;
+
+/// Seconds a press is remembered by default once the character can act, a few frames at 60 fps.
+pub const DEFAULT_BUFFER_WINDOW: f32 = 0.15;
+
+/// The action buttons of `CharacterInput`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum Action {
+    Action1,
+    Action2,
+}
+
+/// One action's button edges and its buffered press.
+#[derive(Debug, Clone)]
+pub struct BufferedAction {
+    /// Seconds a press stays buffered after the button goes down.
+    pub window: f32,
+    held: bool,
+    /// Seconds left before the last press expires, 0 when nothing is buffered.
+    remaining: f32,
+    /// The press is waiting for the clip it was made during to end and doesn't expire yet.
+    waiting: bool,
+}
+
+impl BufferedAction {
+    pub fn new(window: f32) -> Self {
+        Self {
+            window,
+            held: false,
+            remaining: 0.0,
+            waiting: false,
+        }
+    }
+
+    /// Records this frame's button state, returns whether it was just pressed and just released.
+    /// A press made while `hold` is set doesn't expire until it is released.
+    pub fn update(&mut self, held: bool, delta: f32, hold: bool) -> (bool, bool) {
+        let pressed = held && !self.held;
+        let released = !held && self.held;
+        self.held = held;
+
+        if pressed {
+            self.remaining = self.window;
+            self.waiting = hold;
+        } else if !self.waiting {
+            self.remaining = (self.remaining - delta).max(0.0);
+        }
+
+        (pressed, released)
+    }
+
+    /// Lets a waiting press expire from now on.
+    pub fn release(&mut self) {
+        self.waiting = false;
+    }
+
+    /// Whether a press is waiting to be acted on.
+    pub fn buffered(&self) -> bool {
+        self.remaining > 0.0
+    }
+
+    /// Takes the buffered press, if any, so it is only acted on once.
+    pub fn consume(&mut self) -> bool {
+        let buffered = self.buffered();
+        self.remaining = 0.0;
+        self.waiting = false;
+        buffered
+    }
+}
+
+/// Remembers presses of each action for a short while, so one made just before the character
+/// can act, such as during a Stopping clip, is still honoured once it can.
+#[derive(Component, Debug, Clone)]
+pub struct ActionBuffer {
+    pub action1: BufferedAction,
+    pub action2: BufferedAction,
+    /// Presses made while set don't expire until released. The animation graph sets it while a clip
+    /// that can't be interrupted plays and releases them when it ends, so a press made early in a
+    /// long clip is still there once the character can act.
+    pub hold: bool,
+}
+
+impl Default for ActionBuffer {
+    fn default() -> Self {
+        Self {
+            action1: BufferedAction::new(DEFAULT_BUFFER_WINDOW),
+            action2: BufferedAction::new(DEFAULT_BUFFER_WINDOW),
+            hold: false,
+        }
+    }
+}
+
+impl ActionBuffer {
+    pub fn get(&self, action: Action) -> &BufferedAction {
+        match action {
+            Action::Action1 => &self.action1,
+            Action::Action2 => &self.action2,
+        }
+    }
+
+    pub fn get_mut(&mut self, action: Action) -> &mut BufferedAction {
+        match action {
+            Action::Action1 => &mut self.action1,
+            Action::Action2 => &mut self.action2,
+        }
+    }
+
+    /// Lets presses waiting for a clip to end expire from now on.
+    pub fn release(&mut self) {
+        self.action1.release();
+        self.action2.release();
+    }
+}
+
+/// Fills in the just pressed and just released flags of every character's input and buffers its presses.
+/// Buffered presses are acted on by animation graph transitions with a `Pressed` condition.
+pub fn update_action_buffers(time: Res<Time>, mut query: Query<(&mut CharacterInput, &mut ActionBuffer)>) {
+    let delta = time.delta_secs();
+
+    for (mut input, mut buffer) in query.iter_mut() {
+        let hold = buffer.hold;
+        (input.action1_pressed, input.action1_released) = buffer.action1.update(input.action1, delta, hold);
+        (input.action2_pressed, input.action2_released) = buffer.action2.update(input.action2, delta, hold);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Times that add up exactly, the window is 8 frames.
+    const FRAME: f32 = 1.0 / 64.0;
+    const WINDOW: f32 = 0.125;
+
+    /// A buffer pressed on its first update and released on the next.
+    fn tapped(hold: bool) -> BufferedAction {
+        let mut action = BufferedAction::new(WINDOW);
+        assert_eq!(action.update(true, FRAME, hold), (true, false));
+        assert_eq!(action.update(false, FRAME, hold), (false, true));
+        action
+    }
+
+    #[test]
+    fn edges_fire_once() {
+        let mut action = BufferedAction::new(WINDOW);
+        assert_eq!(action.update(false, FRAME, false), (false, false));
+        assert_eq!(action.update(true, FRAME, false), (true, false));
+        assert_eq!(action.update(true, FRAME, false), (false, false));
+        assert_eq!(action.update(false, FRAME, false), (false, true));
+        assert_eq!(action.update(false, FRAME, false), (false, false));
+    }
+
+    #[test]
+    fn presses_expire_after_the_window() {
+        let mut action = tapped(false);
+        assert!(action.buffered());
+
+        // the release frame counted too
+        for _ in 0..6 {
+            action.update(false, FRAME, false);
+        }
+        assert!(action.buffered());
+        action.update(false, FRAME, false);
+        assert!(!action.buffered());
+    }
+
+    #[test]
+    fn holding_the_button_does_not_renew_the_press() {
+        let mut action = BufferedAction::new(WINDOW);
+        action.update(true, FRAME, false);
+        for _ in 0..10 {
+            action.update(true, FRAME, false);
+        }
+        assert!(!action.buffered());
+    }
+
+    #[test]
+    fn presses_are_consumed_once() {
+        let mut action = tapped(false);
+        assert!(action.consume());
+        assert!(!action.consume());
+        assert!(!action.buffered());
+    }
+
+    #[test]
+    fn held_presses_wait_for_release() {
+        let mut action = tapped(true);
+        for _ in 0..120 {
+            action.update(false, FRAME, true);
+        }
+        assert!(action.buffered());
+
+        // once released it gets the usual window
+        action.release();
+        for _ in 0..7 {
+            action.update(false, FRAME, false);
+        }
+        assert!(action.buffered());
+        action.update(false, FRAME, false);
+        assert!(!action.buffered());
+    }
+
+    #[test]
+    fn presses_before_the_hold_still_expire() {
+        let mut action = tapped(false);
+        for _ in 0..10 {
+            action.update(false, FRAME, true);
+        }
+        assert!(!action.buffered());
+    }
+}
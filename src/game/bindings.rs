@@ -0,0 +1,142 @@
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use crate::game::socd::SocdPolicy;
+
+/// A physical control an action can be bound to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    Gamepad(GamepadButton),
+}
+
+impl Binding {
+    pub fn pressed(
+        &self,
+        keyboard: &ButtonInput<KeyCode>,
+        mouse: &ButtonInput<MouseButton>,
+        gamepad: Option<&Gamepad>,
+    ) -> bool {
+        match self {
+            Binding::Key(key) => keyboard.pressed(*key),
+            Binding::Mouse(button) => mouse.pressed(*button),
+            Binding::Gamepad(button) => gamepad.is_some_and(|gamepad| gamepad.pressed(*button)),
+        }
+    }
+}
+
+/// Controls bound to each `CharacterInput` field, holding any of them holds the action.
+/// Loaded from "bindings.ron" in the user's config directory so controls can be remapped without a rebuild.
+#[derive(Resource, Debug, Clone, Serialize, Deserialize)]
+pub struct InputBindings {
+    pub up: Vec<Binding>,
+    pub down: Vec<Binding>,
+    pub left: Vec<Binding>,
+    pub right: Vec<Binding>,
+    pub action1: Vec<Binding>,
+    pub action2: Vec<Binding>,
+    /// What holding opposite directions does.
+    #[serde(default)]
+    pub socd: SocdPolicy,
+}
+
+impl Default for InputBindings {
+    fn default() -> Self {
+        use Binding::*;
+
+        Self {
+            up: vec![Key(KeyCode::ArrowUp), Key(KeyCode::KeyW), Gamepad(GamepadButton::DPadUp)],
+            down: vec![Key(KeyCode::ArrowDown), Key(KeyCode::KeyS), Gamepad(GamepadButton::DPadDown)],
+            left: vec![Key(KeyCode::ArrowLeft), Key(KeyCode::KeyA), Gamepad(GamepadButton::DPadLeft)],
+            right: vec![Key(KeyCode::ArrowRight), Key(KeyCode::KeyD), Gamepad(GamepadButton::DPadRight)],
+            action1: vec![Key(KeyCode::Space), Gamepad(GamepadButton::South)],
+            action2: vec![Key(KeyCode::ShiftLeft), Gamepad(GamepadButton::East)],
+            socd: SocdPolicy::default(),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum BindingsError {
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not parse input bindings: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+    #[error("could not write input bindings: {0}")]
+    Write(#[from] ron::Error),
+}
+
+impl InputBindings {
+    /// Bindings in `CharacterInput` field order.
+    pub fn as_array(&self) -> [&[Binding]; 6] {
+        [
+            &self.up,
+            &self.down,
+            &self.left,
+            &self.right,
+            &self.action1,
+            &self.action2,
+        ]
+    }
+
+    /// Which actions are held, in `CharacterInput` field order.
+    pub fn pressed(
+        &self,
+        keyboard: &ButtonInput<KeyCode>,
+        mouse: &ButtonInput<MouseButton>,
+        gamepad: Option<&Gamepad>,
+    ) -> [bool; 6] {
+        self.as_array()
+            .map(|bindings| bindings.iter().any(|binding| binding.pressed(keyboard, mouse, gamepad)))
+    }
+
+    pub fn load(path: &Path) -> Result<Self, BindingsError> {
+        let text = std::fs::read_to_string(path)?;
+        Ok(ron::from_str(&text)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), BindingsError> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let text = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?;
+        std::fs::write(path, text)?;
+        Ok(())
+    }
+
+    /// The player's bindings, or the defaults when there are none yet, which are then written out
+    /// so there is a file to edit. A broken file is left alone for the player to fix.
+    pub fn load_or_create() -> Self {
+        let Some(path) = bindings_path() else {
+            warn!("no config directory, using default input bindings");
+            return Self::default();
+        };
+
+        match Self::load(&path) {
+            Ok(bindings) => {
+                info!("loaded input bindings from {}", path.display());
+                bindings
+            }
+            Err(BindingsError::Io(e)) if e.kind() == ErrorKind::NotFound => {
+                let bindings = Self::default();
+                match bindings.save(&path) {
+                    Ok(()) => info!("wrote default input bindings to {}", path.display()),
+                    Err(e) => warn!("{}: {}", path.display(), e),
+                }
+                bindings
+            }
+            Err(e) => {
+                warn!("{}: {}, using default input bindings", path.display(), e);
+                Self::default()
+            }
+        }
+    }
+}
+
+/// Where the bindings file lives, such as "~/.config/px_test/bindings.ron" on Linux.
+pub fn bindings_path() -> Option<PathBuf> {
+    Some(dirs::config_dir()?.join(env!("CARGO_PKG_NAME")).join("bindings.ron"))
+}
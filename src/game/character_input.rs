@@ -1,6 +1,6 @@
 use bevy::prelude::*;
 
-#[derive(Component, Clone)]
+#[derive(Component, Clone, Default)]
 pub struct CharacterInput {
     pub up: bool,
     pub down: bool,
@@ -8,22 +8,18 @@ pub struct CharacterInput {
     pub right: bool,
     pub action1: bool,
     pub action2: bool,
-}
-
-impl Default for CharacterInput {
-    fn default() -> Self {
-        Self {
-            up: false,
-            down: false,
-            left: false,
-            right: false,
-            action1: false,
-            action2: false,
-        }
-    }
+    /// True only on the frame the button went down or came back up, filled in by `update_action_buffers`.
+    pub action1_pressed: bool,
+    pub action1_released: bool,
+    pub action2_pressed: bool,
+    pub action2_released: bool,
+    /// Where the character wants to move, at most unit length. Keys give the 8 dpad directions,
+    /// sticks and AI can steer at any angle and tilt less than fully to move slower.
+    pub axis: Vec2,
 }
 
 impl CharacterInput {
+    /// Input from button states, the axis follows the direction buttons.
     pub fn from_array(arr: [bool; 6]) -> Self {
         Self {
             up: arr[0],
@@ -32,6 +28,8 @@ impl CharacterInput {
             right: arr[3],
             action1: arr[4],
             action2: arr[5],
+            axis: dpad_axis([arr[0], arr[1], arr[2], arr[3]]),
+            ..default()
         }
     }
     
@@ -47,17 +45,15 @@ impl CharacterInput {
     }
 }
 
-#[derive(Component)]
-pub struct RandomInput {
-    pub input: CharacterInput,
+/// Unit vector held up, down, left and right buttons point along, opposite buttons cancel out.
+pub fn dpad_axis([up, down, left, right]: [bool; 4]) -> Vec2 {
+    let axis = |positive: bool, negative: bool| positive as i32 as f32 - negative as i32 as f32;
+    Vec2::new(axis(right, left), axis(up, down)).normalize_or_zero()
 }
 
-impl Default for RandomInput {
-    fn default() -> Self {
-        Self {
-            input: CharacterInput::default(),
-        }
-    }
+#[derive(Component, Default)]
+pub struct RandomInput {
+    pub input: CharacterInput,
 }
 
 // update random input system
@@ -70,9 +66,9 @@ pub fn update_random_input(
         
         let mut input_array = random_input.input.as_array();
         
-        for i in 0..input_array.len() {
+        for input in input_array.iter_mut() {
             if rand::random::<f32>() < chance {
-                input_array[i] = !input_array[i];
+                *input = !*input;
             }
         }
     
@@ -1,8 +1,10 @@
 use bevy::prelude::*;
 use enum_iterator::Sequence;
+use serde::{Deserialize, Serialize};
     
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Component, Sequence)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Component, Sequence, Serialize, Deserialize)]
 pub enum CharacterState {
     Still,
     Moving,
+    Running,
 }
\ No newline at end of file
@@ -0,0 +1,69 @@
+use bevy::input::gamepad::{GamepadConnection, GamepadConnectionEvent};
+use bevy::prelude::*;
+use crate::direction::Direction8;
+
+/// Gamepad tuning for the player.
+#[derive(Resource, Clone)]
+pub struct GamepadConfig {
+    /// Stick deflection, from 0 to 1, below which the stick reads as centred.
+    pub dead_zone: f32,
+}
+
+impl Default for GamepadConfig {
+    fn default() -> Self {
+        Self { dead_zone: 0.25 }
+    }
+}
+
+/// The gamepad driving the player, the first one connected until it is unplugged.
+#[derive(Resource, Default)]
+pub struct ActiveGamepad(pub Option<Entity>);
+
+/// Picks up newly connected gamepads and hands over to another one when the active pad is unplugged.
+pub fn update_gamepad_connections(
+    mut connections: EventReader<GamepadConnectionEvent>,
+    mut active: ResMut<ActiveGamepad>,
+    gamepads: Query<(Entity, &Name), With<Gamepad>>,
+) {
+    for event in connections.read() {
+        match &event.connection {
+            GamepadConnection::Connected { name, .. } => {
+                info!("gamepad connected: {}", name);
+                if active.0.is_none() {
+                    active.0 = Some(event.gamepad);
+                }
+            }
+            GamepadConnection::Disconnected => {
+                info!("gamepad disconnected");
+                if active.0 == Some(event.gamepad) {
+                    // the unplugged pad has lost its Gamepad component by now
+                    active.0 = gamepads
+                        .iter()
+                        .find(|(entity, _)| *entity != event.gamepad)
+                        .map(|(entity, name)| {
+                            info!("switched to gamepad {}", name);
+                            entity
+                        });
+                }
+            }
+        }
+    }
+}
+
+/// The left stick as a move axis, zero inside the dead zone and rescaled so tilt
+/// grows smoothly from the edge of the dead zone to full deflection.
+pub fn gamepad_axis(gamepad: &Gamepad, config: &GamepadConfig) -> Vec2 {
+    let stick = gamepad.left_stick();
+    let tilt = stick.length();
+    if tilt <= config.dead_zone {
+        return Vec2::ZERO;
+    }
+
+    stick / tilt * ((tilt - config.dead_zone) / (1.0 - config.dead_zone)).min(1.0)
+}
+
+/// Up, down, left and right as read from the left stick, snapped to the nearest of 8 directions
+/// once it leaves the dead zone. Gamepad buttons are bound through `InputBindings`.
+pub fn gamepad_stick(gamepad: &Gamepad, config: &GamepadConfig) -> [bool; 4] {
+    Direction8::from_vector(gamepad_axis(gamepad, config)).map_or([false; 4], Direction8::to_dpad)
+}
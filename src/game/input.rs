@@ -2,45 +2,68 @@ use bevy::prelude::*;
 use crate::direction::Direction8;
 use crate::game::character_state::CharacterState;
 use crate::game::character_input::CharacterInput;
+use crate::game::movement::{Acceleration, Facing, GroundSpeed, MoveSpeed, SpeedRamp, Velocity};
+use crate::rendering::animation_graph::AnimationParams;
+use crate::rendering::sprite_state::SpriteState;
+
+/// Components a character moves with.
+type Movement = (
+    &'static mut Transform,
+    &'static mut Facing,
+    &'static MoveSpeed,
+    &'static Acceleration,
+    &'static mut SpeedRamp,
+    &'static mut Velocity,
+    &'static mut GroundSpeed,
+    &'static mut AnimationParams,
+);
 
 pub fn update_characters(
-    mut query: Query<(&mut Direction8, &mut CharacterState, &mut Transform, &CharacterInput)>,
+    time: Res<Time>,
+    mut query: Query<(&mut Direction8, &mut CharacterState, &CharacterInput, &SpriteState, Movement)>,
 ) {
     // get player and npc inputs here if needed
-    for (mut direction, mut state, mut transform, input) in query.iter_mut() {
-        let (new_direction, new_state) = directional_input(input.as_array()[0..4].try_into().unwrap());
+    for (mut direction, mut state, input, clip, movement) in query.iter_mut() {
+        let (mut transform, mut facing, move_speed, acceleration, mut ramp, mut velocity, mut ground_speed, mut params) =
+            movement;
+
+        let axis = input.axis.clamp_length_max(1.0);
+        let (new_direction, new_state) = directional_input(axis);
 
         if let Some(dir) = new_direction {
             *direction = dir;
+            facing.0 = axis;
         }
-        *state = new_state;
+        // holding action2 runs
+        *state = if new_state == CharacterState::Moving && input.action2 {
+            CharacterState::Running
+        } else {
+            new_state
+        };
 
-        // Simple movement test
-        if *state == CharacterState::Moving {
-            transform.translation += direction.to_translation().extend(0.0) * 0.17;
+        // the true axis, so a half tilted stick walks at half speed
+        let target = match *state {
+            CharacterState::Moving => axis * move_speed.walk,
+            CharacterState::Running => axis * move_speed.run,
+            CharacterState::Still => Vec2::ZERO,
+        };
+        // the stop ramp starts with the Stopping clip, so a walk or run cycle that can't be
+        // interrupted carries on at its speed until it ends
+        let clip_playing = matches!(clip, SpriteState::Starting | SpriteState::Moving | SpriteState::Running);
+        if target != Vec2::ZERO || !clip_playing {
+            velocity.0 = acceleration.approach(&mut ramp, velocity.0, target, time.delta_secs());
         }
+        transform.translation += velocity.0.extend(0.0) * time.delta_secs();
+
+        let speed = velocity.0.length();
+        ground_speed.0 = speed;
+        params.set("speed", speed);
     }
 }
 
-    
-/// Converts keys to dpad style movement and state
-pub fn directional_input(input: [bool; 4]) -> (Option<Direction8>, CharacterState) {
-    let up = input[0];
-    let down = input[1];
-    let left = input[2];
-    let right = input[3];
-
-    let new_direction = match (up, down, left, right) {
-        (true, false, false, false) => Some(Direction8::North),
-        (true, false, true, false) => Some(Direction8::Northwest),
-        (true, false, false, true) => Some(Direction8::Northeast),
-        (false, true, false, false) => Some(Direction8::South),
-        (false, true, true, false) => Some(Direction8::Southwest),
-        (false, true, false, true) => Some(Direction8::Southeast),
-        (false, false, true, false) => Some(Direction8::West),
-        (false, false, false, true) => Some(Direction8::East),
-        _ => None,
-    };
+/// Facing and state for a move axis, the facing is the nearest of the 8 directions.
+pub fn directional_input(axis: Vec2) -> (Option<Direction8>, CharacterState) {
+    let new_direction = Direction8::from_vector(axis);
 
     let new_state = if new_direction.is_some() {
         CharacterState::Moving
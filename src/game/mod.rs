@@ -1,4 +1,10 @@
 pub mod input;
 pub mod character_input;
 pub mod character_state;
-pub mod player_input;
\ No newline at end of file
+pub mod player_input;
+pub mod movement;
+pub mod gamepad_input;
+pub mod bindings;
+pub mod socd;
+pub mod action_buffer;
+pub mod replay;
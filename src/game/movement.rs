@@ -0,0 +1,75 @@
+use bevy::prelude::*;
+
+/// How fast a character moves, in world units per second.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct MoveSpeed {
+    pub walk: f32,
+    pub run: f32,
+}
+
+impl Default for MoveSpeed {
+    fn default() -> Self {
+        MoveSpeed {
+            walk: 10.0,
+            run: 20.0,
+        }
+    }
+}
+
+/// World units per second the character actually moved during the last update.
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct GroundSpeed(pub f32);
+
+/// World units per second the character is moving at, eased towards its input by `Acceleration`.
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct Velocity(pub Vec2);
+
+/// The way the character faces, as the move axis it last moved along. Sprite sets pick their
+/// nearest facing from it, so a 16 direction set can show more than the 8 `Direction8` facings.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Facing(pub Vec2);
+
+impl Default for Facing {
+    fn default() -> Self {
+        Facing(Vec2::X)
+    }
+}
+
+/// Seconds to ramp from standing to moving speed, and from moving speed back to standing.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Acceleration {
+    pub start: f32,
+    pub stop: f32,
+}
+
+impl Default for Acceleration {
+    fn default() -> Self {
+        Acceleration { start: 1.0, stop: 1.0 }
+    }
+}
+
+/// The speed change a character is part way through, see `Acceleration::approach`.
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct SpeedRamp {
+    target: Vec2,
+    /// World units per second the velocity changes by each second.
+    rate: f32,
+}
+
+impl Acceleration {
+    /// Moves `velocity` towards `target`. A new target starts a ramp that covers the whole change
+    /// in `start` seconds, or `stop` seconds when coming to a halt, whether walking or running.
+    /// A zero ramp changes speed instantly.
+    pub fn approach(&self, ramp: &mut SpeedRamp, velocity: Vec2, target: Vec2, delta: f32) -> Vec2 {
+        let seconds = if target == Vec2::ZERO { self.stop } else { self.start };
+        if seconds <= 0.0 {
+            return target;
+        }
+
+        if target != ramp.target {
+            ramp.target = target;
+            ramp.rate = (target - velocity).length() / seconds;
+        }
+        velocity.move_towards(target, ramp.rate * delta)
+    }
+}
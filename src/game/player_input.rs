@@ -1,5 +1,8 @@
 use bevy::prelude::*;
 use crate::game::character_input::CharacterInput;
+use crate::game::bindings::InputBindings;
+use crate::game::gamepad_input::{ActiveGamepad, GamepadConfig, gamepad_axis, gamepad_stick};
+use crate::game::socd::PressOrder;
 
 #[derive(Resource, Clone)]
 pub struct PlayerInput {
@@ -7,6 +10,8 @@ pub struct PlayerInput {
     pub click_l: bool,
     pub click_r: bool,
     pub pointer: Vec2,
+    /// Order the direction buttons went down in, for resolving opposites.
+    pub press_order: PressOrder,
 }
 
 impl Default for PlayerInput {
@@ -16,38 +21,50 @@ impl Default for PlayerInput {
             click_l: false,
             click_r: false,
             pointer: Vec2::ZERO,
+            press_order: PressOrder::default(),
         }
     }
 }
 
-/// Read keyboard state into the global PlayerInput resource.
+/// Read keyboard, mouse and gamepad state through the player's bindings into the global PlayerInput resource.
+#[allow(clippy::too_many_arguments)]
 pub fn update_player_input(
     mut input: ResMut<PlayerInput>,
     windows: Query<&Window>,
     keyboard: Res<ButtonInput<KeyCode>>,
     mouse: Res<ButtonInput<MouseButton>>,
+    bindings: Res<InputBindings>,
+    active_gamepad: Res<ActiveGamepad>,
+    gamepad_config: Res<GamepadConfig>,
+    gamepads: Query<&Gamepad>,
     mut query: Query<(&mut PlayerControl, &mut CharacterInput)>,
 ) {
-    use KeyCode::*;
+    let gamepad = active_gamepad.0.and_then(|entity| gamepads.get(entity).ok());
+    let mut pressed = bindings.pressed(&keyboard, &mouse, gamepad);
 
-    // movement is true if either arrow keys OR WASD are pressed
-    input.input.up = keyboard.pressed(ArrowUp) || keyboard.pressed(KeyCode::KeyW);
-    input.input.down = keyboard.pressed(ArrowDown) || keyboard.pressed(KeyCode::KeyS);
-    input.input.left = keyboard.pressed(ArrowLeft) || keyboard.pressed(KeyCode::KeyA);
-    input.input.right = keyboard.pressed(ArrowRight) || keyboard.pressed(KeyCode::KeyD);
-
-    // button mappings (change as needed)
-    input.input.action1 = keyboard.pressed(Space);
-    input.input.action2 = keyboard.pressed(ShiftLeft);
+    // the stick adds to whatever the direction bindings say
+    let stick = gamepad.map_or(Vec2::ZERO, |gamepad| gamepad_axis(gamepad, &gamepad_config));
+    if let Some(gamepad) = gamepad {
+        for (held, tilted) in pressed.iter_mut().zip(gamepad_stick(gamepad, &gamepad_config)) {
+            *held |= tilted;
+        }
+    }
+    let dpad = input.press_order.resolve([pressed[0], pressed[1], pressed[2], pressed[3]], bindings.socd);
+    pressed[..4].copy_from_slice(&dpad);
+    input.input = CharacterInput::from_array(pressed);
+    // a tilted stick steers at its own angle and speed rather than the 8 button directions
+    if stick != Vec2::ZERO {
+        input.input.axis = stick;
+    }
 
     // mouse buttons
     input.click_l = mouse.pressed(MouseButton::Left);
     input.click_r = mouse.pressed(MouseButton::Right);
     
-    if let Ok(window) = windows.single() {
-        if let Some(pos) = window.cursor_position() {
-            input.pointer = pos;
-        }
+    if let Ok(window) = windows.single()
+        && let Some(pos) = window.cursor_position()
+    {
+        input.pointer = pos;
     }
 
     for (mut control, mut character_input) in query.iter_mut() {
@@ -56,15 +73,7 @@ pub fn update_player_input(
     }
 }
 
-#[derive(Component)]
+#[derive(Component, Default)]
 pub struct PlayerControl {
     pub player_input: PlayerInput,
-}
-
-impl Default for PlayerControl {
-    fn default() -> Self {
-        Self {
-            player_input: PlayerInput::default(),
-        }
-    }
 }
\ No newline at end of file
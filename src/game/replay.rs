@@ -0,0 +1,433 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use bevy::prelude::*;
+use bevy::time::TimeUpdateStrategy;
+use thiserror::Error;
+use crate::game::character_input::CharacterInput;
+use crate::spawn::PendingSprites;
+
+/// First bytes of a replay file, followed by the format version byte and then the ticks.
+const MAGIC: &[u8; 4] = b"PXRP";
+const VERSION: u8 = 1;
+
+/// Identifies a character in replay files, the same on every run unlike its `Entity`.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReplayId(pub u32);
+
+/// One character's input on one tick and where it stood after moving.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayInput {
+    pub id: u32,
+    /// `CharacterInput::as_array` packed into bits, up in the lowest.
+    pub buttons: u8,
+    pub axis: Vec2,
+    pub position: Vec2,
+}
+
+/// Everything recorded on one tick, the frame time included so playback steps the same.
+#[derive(Debug, Clone, Default)]
+pub struct ReplayTick {
+    pub delta: Duration,
+    pub inputs: Vec<ReplayInput>,
+}
+
+impl ReplayInput {
+    pub fn new(id: ReplayId, input: &CharacterInput, position: Vec2) -> Self {
+        let buttons = input
+            .as_array()
+            .iter()
+            .enumerate()
+            .fold(0, |bits, (index, held)| bits | (*held as u8) << index);
+
+        Self {
+            id: id.0,
+            buttons,
+            axis: input.axis,
+            position,
+        }
+    }
+
+    pub fn to_input(&self) -> CharacterInput {
+        let mut input = CharacterInput::from_array(std::array::from_fn(|index| self.buttons & (1 << index) != 0));
+        // a stick or AI axis is not the one the buttons make
+        input.axis = self.axis;
+        input
+    }
+}
+
+fn take<const N: usize>(bytes: &mut &[u8]) -> Option<[u8; N]> {
+    let (head, rest) = bytes.split_first_chunk::<N>()?;
+    *bytes = rest;
+    Some(*head)
+}
+
+fn take_f32(bytes: &mut &[u8]) -> Option<f32> {
+    take(bytes).map(f32::from_le_bytes)
+}
+
+impl ReplayTick {
+    /// Little endian: delta in nanoseconds as u64, input count as u16, then per input the id as u32,
+    /// the buttons byte, and the axis and position as f32 pairs.
+    pub fn write(&self, out: &mut impl Write) -> std::io::Result<()> {
+        out.write_all(&(self.delta.as_nanos() as u64).to_le_bytes())?;
+        out.write_all(&(self.inputs.len() as u16).to_le_bytes())?;
+        for input in &self.inputs {
+            out.write_all(&input.id.to_le_bytes())?;
+            out.write_all(&[input.buttons])?;
+            for value in [input.axis.x, input.axis.y, input.position.x, input.position.y] {
+                out.write_all(&value.to_le_bytes())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads the tick at the front of `bytes`, None when there isn't a whole one left.
+    pub fn read(bytes: &mut &[u8]) -> Option<Self> {
+        let delta = Duration::from_nanos(u64::from_le_bytes(take(bytes)?));
+        let count = u16::from_le_bytes(take(bytes)?);
+
+        let mut inputs = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            inputs.push(ReplayInput {
+                id: u32::from_le_bytes(take(bytes)?),
+                buttons: take::<1>(bytes)?[0],
+                axis: Vec2::new(take_f32(bytes)?, take_f32(bytes)?),
+                position: Vec2::new(take_f32(bytes)?, take_f32(bytes)?),
+            });
+        }
+
+        Some(Self { delta, inputs })
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum ReplayError {
+    #[error("{0}: {1}")]
+    Io(PathBuf, std::io::Error),
+    #[error("{0}: not a replay file")]
+    Magic(PathBuf),
+    #[error("{0}: replay format version {1}, this build reads version {VERSION}")]
+    Version(PathBuf, u8),
+}
+
+/// Reads every tick of a replay file. A tick cut short, say by a crash while recording, is dropped.
+pub fn load_replay(path: &Path) -> Result<Vec<ReplayTick>, ReplayError> {
+    let data = std::fs::read(path).map_err(|e| ReplayError::Io(path.to_path_buf(), e))?;
+
+    let mut bytes = data.as_slice();
+    if take::<4>(&mut bytes).as_ref() != Some(MAGIC) {
+        return Err(ReplayError::Magic(path.to_path_buf()));
+    }
+    match take::<1>(&mut bytes) {
+        Some([VERSION]) => {}
+        Some([version]) => return Err(ReplayError::Version(path.to_path_buf(), version)),
+        None => return Err(ReplayError::Magic(path.to_path_buf())),
+    }
+
+    let mut ticks = Vec::new();
+    while let Some(tick) = ReplayTick::read(&mut bytes) {
+        ticks.push(tick);
+    }
+    if !bytes.is_empty() {
+        warn!("{}: {} bytes of an unfinished tick at the end, ignored", path.display(), bytes.len());
+    }
+
+    Ok(ticks)
+}
+
+/// What the command line asked for, `--record <file>` or `--replay <file>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplayArg {
+    Record(PathBuf),
+    Play(PathBuf),
+}
+
+pub fn parse_replay_args(args: impl IntoIterator<Item = String>) -> Option<ReplayArg> {
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--record" => return args.next().map(|path| ReplayArg::Record(path.into())),
+            "--replay" => return args.next().map(|path| ReplayArg::Play(path.into())),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Writes the input of every `ReplayId` character to a file each tick, flushed as it goes
+/// so a crash still leaves a replay to attach to the bug report.
+#[derive(Resource)]
+pub struct InputRecorder {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    started: bool,
+}
+
+impl InputRecorder {
+    pub fn create(path: &Path) -> Result<Self, ReplayError> {
+        let io_error = |e| ReplayError::Io(path.to_path_buf(), e);
+
+        let mut writer = BufWriter::new(File::create(path).map_err(io_error)?);
+        writer.write_all(MAGIC).map_err(io_error)?;
+        writer.write_all(&[VERSION]).map_err(io_error)?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            writer,
+            started: false,
+        })
+    }
+}
+
+/// Feeds recorded input back into the characters in place of the player and `RandomInput`,
+/// checking each tick that every character ends up where it did when recorded.
+#[derive(Resource)]
+pub struct InputPlayback {
+    path: PathBuf,
+    ticks: Vec<ReplayTick>,
+    next: usize,
+    started: bool,
+    mismatches: usize,
+}
+
+impl InputPlayback {
+    pub fn load(path: &Path) -> Result<Self, ReplayError> {
+        Ok(Self {
+            path: path.to_path_buf(),
+            ticks: load_replay(path)?,
+            next: 0,
+            started: false,
+            mismatches: 0,
+        })
+    }
+
+    /// Reports how the run compared and quits, failing when positions diverged so CI can catch it.
+    fn finish(&self, exit: &mut EventWriter<AppExit>) {
+        if self.mismatches == 0 {
+            info!("{}: replayed {} ticks, positions match", self.path.display(), self.ticks.len());
+            exit.write(AppExit::Success);
+        } else {
+            error!(
+                "{}: replayed {} ticks, {} positions differ from the recording",
+                self.path.display(),
+                self.ticks.len(),
+                self.mismatches
+            );
+            exit.write(AppExit::error());
+        }
+    }
+}
+
+/// Whether player and random input are read. Playback replaces them, and while recording they are
+/// held back until the recording starts so it starts with every character at rest.
+pub fn live_input(recorder: Option<Res<InputRecorder>>, playback: Option<Res<InputPlayback>>) -> bool {
+    playback.is_none() && recorder.is_none_or(|recorder| recorder.started)
+}
+
+/// Starts recording or playback once every character's sprites have loaded, as loading takes
+/// a different number of frames on every run. Runs last so both start on the following tick.
+pub fn start_replays(
+    pending: Query<(), With<PendingSprites>>,
+    recorder: Option<ResMut<InputRecorder>>,
+    playback: Option<ResMut<InputPlayback>>,
+    mut strategy: ResMut<TimeUpdateStrategy>,
+    mut exit: EventWriter<AppExit>,
+) {
+    if !pending.is_empty() {
+        return;
+    }
+
+    if let Some(mut recorder) = recorder
+        && !recorder.started
+    {
+        recorder.started = true;
+        info!("recording input to {}", recorder.path.display());
+    }
+
+    if let Some(mut playback) = playback
+        && !playback.started
+    {
+        playback.started = true;
+        info!("playing back {} ticks from {}", playback.ticks.len(), playback.path.display());
+        match playback.ticks.first() {
+            Some(tick) => *strategy = TimeUpdateStrategy::ManualDuration(tick.delta),
+            None => playback.finish(&mut exit),
+        }
+    }
+}
+
+pub fn record_inputs(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut recorder: ResMut<InputRecorder>,
+    query: Query<(&ReplayId, &CharacterInput, &Transform)>,
+) {
+    if !recorder.started {
+        return;
+    }
+
+    let mut inputs: Vec<ReplayInput> = query
+        .iter()
+        .map(|(id, input, transform)| ReplayInput::new(*id, input, transform.translation.truncate()))
+        .collect();
+    inputs.sort_by_key(|input| input.id);
+
+    let tick = ReplayTick { delta: time.delta(), inputs };
+    if let Err(e) = tick.write(&mut recorder.writer).and_then(|()| recorder.writer.flush()) {
+        error!("{}: {}, recording stopped", recorder.path.display(), e);
+        commands.remove_resource::<InputRecorder>();
+    }
+}
+
+/// Hands each character its recorded input for this tick.
+pub fn play_back_inputs(playback: Res<InputPlayback>, mut query: Query<(&ReplayId, &mut CharacterInput)>) {
+    if !playback.started {
+        return;
+    }
+    let Some(tick) = playback.ticks.get(playback.next) else {
+        return;
+    };
+
+    for (id, mut input) in query.iter_mut() {
+        if let Some(recorded) = tick.inputs.iter().find(|recorded| recorded.id == id.0) {
+            *input = recorded.to_input();
+        }
+    }
+}
+
+/// Compares where the characters ended up with the recording, then steps time by the next tick's delta.
+pub fn verify_playback(
+    mut playback: ResMut<InputPlayback>,
+    mut strategy: ResMut<TimeUpdateStrategy>,
+    mut exit: EventWriter<AppExit>,
+    query: Query<(&ReplayId, &Transform)>,
+) {
+    if !playback.started {
+        return;
+    }
+    let Some(tick) = playback.ticks.get(playback.next) else {
+        return;
+    };
+
+    let mut mismatches = 0;
+    for recorded in &tick.inputs {
+        let found = query
+            .iter()
+            .find(|(id, _)| id.0 == recorded.id)
+            .map(|(_, transform)| transform.translation.truncate());
+        if found != Some(recorded.position) {
+            if playback.mismatches + mismatches == 0 {
+                warn!(
+                    "{}: tick {}: character {} is at {}, recorded at {}",
+                    playback.path.display(),
+                    playback.next,
+                    recorded.id,
+                    found.map_or("nowhere".to_string(), |position| position.to_string()),
+                    recorded.position
+                );
+            }
+            mismatches += 1;
+        }
+    }
+    playback.mismatches += mismatches;
+
+    playback.next += 1;
+    match playback.ticks.get(playback.next) {
+        Some(tick) => *strategy = TimeUpdateStrategy::ManualDuration(tick.delta),
+        None => playback.finish(&mut exit),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ticks() -> Vec<ReplayTick> {
+        let mut input = CharacterInput::from_array([true, false, false, true, false, true]);
+        // a half tilted stick, not the dpad axis the buttons give
+        input.axis = Vec2::new(0.25, -0.5);
+
+        vec![
+            ReplayTick {
+                delta: Duration::from_millis(16),
+                inputs: vec![
+                    ReplayInput::new(ReplayId(0), &input, Vec2::new(12.5, -3.0)),
+                    ReplayInput::new(ReplayId(7), &CharacterInput::default(), Vec2::ZERO),
+                ],
+            },
+            ReplayTick {
+                delta: Duration::from_nanos(16_666_667),
+                inputs: Vec::new(),
+            },
+        ]
+    }
+
+    fn encode(ticks: &[ReplayTick]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for tick in ticks {
+            tick.write(&mut bytes).unwrap();
+        }
+        bytes
+    }
+
+    fn assert_same(read: &[ReplayTick], written: &[ReplayTick]) {
+        assert_eq!(read.len(), written.len());
+        for (read, written) in read.iter().zip(written) {
+            assert_eq!(read.delta, written.delta);
+            assert_eq!(read.inputs, written.inputs);
+        }
+    }
+
+    fn temp_file(test: &str, bytes: &[u8]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("px-replay-{}-{}", test, std::process::id()));
+        std::fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    #[test]
+    fn input_round_trip() {
+        let mut input = CharacterInput::from_array([false, true, true, false, true, false]);
+        input.axis = Vec2::new(-0.3, 0.1);
+
+        let replayed = ReplayInput::new(ReplayId(3), &input, Vec2::ONE).to_input();
+        assert_eq!(replayed.as_array(), input.as_array());
+        assert_eq!(replayed.axis, input.axis);
+    }
+
+    #[test]
+    fn tick_round_trip() {
+        let written = ticks();
+        let bytes = encode(&written);
+
+        let mut rest = bytes.as_slice();
+        let read: Vec<_> = std::iter::from_fn(|| ReplayTick::read(&mut rest)).collect();
+        assert_same(&read, &written);
+        assert!(rest.is_empty());
+    }
+
+    #[test]
+    fn load_drops_an_unfinished_tick() {
+        let written = ticks();
+        let mut bytes = [MAGIC.as_slice(), &[VERSION]].concat();
+        bytes.extend(encode(&written));
+        // a crash part way through the next tick
+        bytes.extend(&encode(&written[..1])[..9]);
+
+        let path = temp_file("unfinished", &bytes);
+        let read = load_replay(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_same(&read, &written);
+    }
+
+    #[test]
+    fn load_rejects_other_files() {
+        let path = temp_file("magic", b"PNG\0\x01");
+        assert!(matches!(load_replay(&path), Err(ReplayError::Magic(_))));
+
+        std::fs::write(&path, [MAGIC.as_slice(), &[VERSION + 1]].concat()).unwrap();
+        assert!(matches!(load_replay(&path), Err(ReplayError::Version(_, version)) if version == VERSION + 1));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,138 @@
+use serde::{Deserialize, Serialize};
+
+/// How simultaneous opposite cardinal directions, up with down or left with right, resolve.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum SocdPolicy {
+    /// Opposites cancel out and that axis reads as centred.
+    Neutral,
+    /// The more recently pressed direction wins.
+    #[default]
+    LastInputWins,
+    /// The direction held first wins until it is released.
+    FirstInputWins,
+    /// Up beats down, left with right cancels out like `Neutral`.
+    UpPriority,
+}
+
+/// Indices of opposite directions in `CharacterInput` field order, up and down then left and right.
+const OPPOSITES: [(usize, usize); 2] = [(0, 1), (2, 3)];
+
+/// The held direction buttons in the order they were pressed, oldest first.
+#[derive(Debug, Clone, Default)]
+pub struct PressOrder {
+    held: Vec<usize>,
+}
+
+impl PressOrder {
+    /// Records this frame's up, down, left and right and returns them with opposites resolved by `policy`.
+    /// Buttons pressed on the same frame count as pressed in field order.
+    pub fn resolve(&mut self, dpad: [bool; 4], policy: SocdPolicy) -> [bool; 4] {
+        self.held.retain(|&index| dpad[index]);
+        for (index, held) in dpad.into_iter().enumerate() {
+            if held && !self.held.contains(&index) {
+                self.held.push(index);
+            }
+        }
+
+        let mut resolved = dpad;
+        for (a, b) in OPPOSITES {
+            if !(dpad[a] && dpad[b]) {
+                continue;
+            }
+
+            let a_first = self.rank(a) < self.rank(b);
+            let keep = match policy {
+                SocdPolicy::Neutral => None,
+                SocdPolicy::LastInputWins => Some(if a_first { b } else { a }),
+                SocdPolicy::FirstInputWins => Some(if a_first { a } else { b }),
+                SocdPolicy::UpPriority => (a == 0).then_some(a),
+            };
+            resolved[a] = keep == Some(a);
+            resolved[b] = keep == Some(b);
+        }
+
+        resolved
+    }
+
+    fn rank(&self, index: usize) -> Option<usize> {
+        self.held.iter().position(|&held| held == index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NONE: [bool; 4] = [false; 4];
+    const UP: [bool; 4] = [true, false, false, false];
+    const DOWN: [bool; 4] = [false, true, false, false];
+    const UP_DOWN: [bool; 4] = [true, true, false, false];
+    const UP_DOWN_LEFT: [bool; 4] = [true, true, true, false];
+    const LEFT_RIGHT: [bool; 4] = [false, false, true, true];
+    const LEFT: [bool; 4] = [false, false, true, false];
+
+    /// Feeds one frame of held buttons at a time, returning the last frame resolved.
+    fn resolve(policy: SocdPolicy, frames: &[[bool; 4]]) -> [bool; 4] {
+        let mut order = PressOrder::default();
+        frames.iter().fold(NONE, |_, &dpad| order.resolve(dpad, policy))
+    }
+
+    #[test]
+    fn single_directions_pass_through() {
+        for policy in [
+            SocdPolicy::Neutral,
+            SocdPolicy::LastInputWins,
+            SocdPolicy::FirstInputWins,
+            SocdPolicy::UpPriority,
+        ] {
+            assert_eq!(resolve(policy, &[UP]), UP);
+            assert_eq!(resolve(policy, &[LEFT, [true, false, true, false]]), [true, false, true, false]);
+        }
+    }
+
+    #[test]
+    fn neutral_cancels_opposites() {
+        assert_eq!(resolve(SocdPolicy::Neutral, &[UP, UP_DOWN]), NONE);
+        assert_eq!(resolve(SocdPolicy::Neutral, &[LEFT_RIGHT]), NONE);
+        assert_eq!(resolve(SocdPolicy::Neutral, &[UP, UP_DOWN_LEFT]), LEFT);
+    }
+
+    #[test]
+    fn last_input_wins() {
+        assert_eq!(resolve(SocdPolicy::LastInputWins, &[UP, UP_DOWN]), DOWN);
+        assert_eq!(resolve(SocdPolicy::LastInputWins, &[DOWN, UP_DOWN]), UP);
+        assert_eq!(resolve(SocdPolicy::LastInputWins, &[DOWN, UP_DOWN_LEFT]), [true, false, true, false]);
+        // releasing the newer press hands back to the one still held
+        assert_eq!(resolve(SocdPolicy::LastInputWins, &[UP, UP_DOWN, UP]), UP);
+        // pressing again makes it the newest
+        assert_eq!(resolve(SocdPolicy::LastInputWins, &[UP, UP_DOWN, UP, UP_DOWN]), DOWN);
+    }
+
+    #[test]
+    fn first_input_wins() {
+        assert_eq!(resolve(SocdPolicy::FirstInputWins, &[UP, UP_DOWN]), UP);
+        assert_eq!(resolve(SocdPolicy::FirstInputWins, &[DOWN, UP_DOWN]), DOWN);
+        assert_eq!(resolve(SocdPolicy::FirstInputWins, &[DOWN, UP_DOWN_LEFT]), [false, true, true, false]);
+        // once the first is released the other takes over
+        assert_eq!(resolve(SocdPolicy::FirstInputWins, &[UP, UP_DOWN, DOWN]), DOWN);
+    }
+
+    #[test]
+    fn up_priority() {
+        assert_eq!(resolve(SocdPolicy::UpPriority, &[UP, UP_DOWN]), UP);
+        assert_eq!(resolve(SocdPolicy::UpPriority, &[DOWN, UP_DOWN]), UP);
+        assert_eq!(resolve(SocdPolicy::UpPriority, &[DOWN, UP_DOWN_LEFT]), [true, false, true, false]);
+        assert_eq!(resolve(SocdPolicy::UpPriority, &[LEFT, LEFT_RIGHT]), NONE);
+    }
+
+    #[test]
+    fn same_frame_presses_count_in_field_order() {
+        // up and down land together, down comes after up in field order
+        assert_eq!(resolve(SocdPolicy::LastInputWins, &[UP_DOWN]), DOWN);
+        assert_eq!(resolve(SocdPolicy::FirstInputWins, &[UP_DOWN]), UP);
+        // left before right
+        assert_eq!(resolve(SocdPolicy::LastInputWins, &[LEFT_RIGHT]), [false, false, false, true]);
+        assert_eq!(resolve(SocdPolicy::FirstInputWins, &[LEFT_RIGHT]), LEFT);
+        assert_eq!(resolve(SocdPolicy::LastInputWins, &[UP_DOWN_LEFT]), [false, true, true, false]);
+    }
+}
@@ -0,0 +1,6 @@
+pub mod direction;
+pub mod spawn;
+pub mod rendering;
+pub mod game;
+pub mod pack;
+pub mod validate;
@@ -1,28 +1,92 @@
 use bevy::prelude::*;
-mod direction;
-mod spawn;
-mod rendering;
-mod game;
+use px_test::{game, rendering, spawn};
 
 use rendering::sprite_render::{setup_camera, animate_sprites, update_character_sprites};
+use rendering::sprite_set::{SpriteSet, SpriteSetLoader};
+use rendering::aseprite::AsepriteLoader;
+use rendering::sprite_library::SpriteSetLibrary;
+use rendering::sprite_state::AnimationEvent;
+use rendering::y_sort::y_sort;
+use rendering::animation_graph::{AnimationGraph, AnimationGraphLoader, evaluate_animation_graphs};
 
 use game::player_input::{PlayerInput, update_player_input};
+use game::gamepad_input::{ActiveGamepad, GamepadConfig, update_gamepad_connections};
+use game::bindings::InputBindings;
 use game::character_input::update_random_input;
+use game::action_buffer::update_action_buffers;
+use game::replay::{InputPlayback, InputRecorder, ReplayArg, parse_replay_args};
+use game::replay::{live_input, play_back_inputs, record_inputs, start_replays, verify_playback};
 use game::input::update_characters;
 
-use spawn::{spawn_player, spawn_characters};
+use spawn::{spawn_player, spawn_characters, attach_character_sprites, reload_character_sprites};
+use spawn::match_ramps_to_clips;
 
 fn main() {
     let mut app = App::new();
 
     app.add_plugins(DefaultPlugins.set(ImagePlugin::default_nearest()))
-        .insert_resource(PlayerInput::default()) // global keyboard + mouse input
+        .init_asset::<SpriteSet>()
+        .init_asset_loader::<SpriteSetLoader>()
+        .init_asset_loader::<AsepriteLoader>()
+        .init_asset::<AnimationGraph>()
+        .init_asset_loader::<AnimationGraphLoader>()
+        .init_resource::<SpriteSetLibrary>()
+        .add_event::<AnimationEvent>()
+        .insert_resource(PlayerInput::default()) // global keyboard + mouse + gamepad input
+        .insert_resource(InputBindings::load_or_create())
+        .init_resource::<GamepadConfig>()
+        .init_resource::<ActiveGamepad>()
         .add_systems(Startup, setup_camera)
         .add_systems(Startup, spawn_player)
         .add_systems(Startup, spawn_characters)
-        .add_systems(Update, (update_player_input, update_random_input))
-        .add_systems(Update,  update_characters)
-        .add_systems(Update, (update_character_sprites, animate_sprites));
+        .add_systems(Update, (reload_character_sprites, attach_character_sprites))
+        .add_systems(Update, (update_gamepad_connections, update_player_input.run_if(live_input)).chain())
+        .add_systems(Update, update_random_input.run_if(live_input))
+        .add_systems(Update, play_back_inputs.run_if(resource_exists::<InputPlayback>))
+        .add_systems(
+            Update,
+            update_action_buffers
+                .after(update_player_input)
+                .after(update_random_input)
+                .after(play_back_inputs)
+                .before(update_characters),
+        )
+        .add_systems(Update, (match_ramps_to_clips, update_characters).chain())
+        .add_systems(
+            Update,
+            (
+                record_inputs.run_if(resource_exists::<InputRecorder>),
+                verify_playback.run_if(resource_exists::<InputPlayback>),
+                start_replays,
+            )
+                .chain()
+                .after(update_characters),
+        )
+        .add_systems(Update, (evaluate_animation_graphs, update_character_sprites, animate_sprites).chain())
+        .add_systems(PostUpdate, y_sort.before(TransformSystem::TransformPropagate));
+
+    // --record <file> saves every character's input, --replay <file> plays it back and checks positions
+    match parse_replay_args(std::env::args().skip(1)) {
+        Some(ReplayArg::Record(path)) => match InputRecorder::create(&path) {
+            Ok(recorder) => {
+                app.insert_resource(recorder);
+            }
+            Err(e) => {
+                eprintln!("could not record input: {}", e);
+                std::process::exit(1);
+            }
+        },
+        Some(ReplayArg::Play(path)) => match InputPlayback::load(&path) {
+            Ok(playback) => {
+                app.insert_resource(playback);
+            }
+            Err(e) => {
+                eprintln!("could not load replay: {}", e);
+                std::process::exit(1);
+            }
+        },
+        None => {}
+    }
 
     app.run();
 }
@@ -0,0 +1,372 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use bevy::math::{URect, UVec2};
+use image::{RgbaImage, imageops};
+use thiserror::Error;
+use crate::direction::{Direction16, DirectionResolution};
+use crate::rendering::sprite_set::{DEFAULT_FRAME_TIME, SheetManifest, SpriteSetManifest, manifest_options};
+use crate::rendering::sprite_state::{PlaybackMode, SpriteState};
+
+#[derive(Debug, Error)]
+pub enum PackError {
+    #[error("{0}: {1}")]
+    Io(PathBuf, std::io::Error),
+    #[error("{0}: {1}")]
+    Image(PathBuf, image::ImageError),
+    #[error("{prefix}: {frame} is {found}, expected {expected} like the first frame")]
+    FrameSize {
+        prefix: String,
+        frame: PathBuf,
+        found: UVec2,
+        expected: UVec2,
+    },
+    #[error("{prefix}: frames are {found}, expected {expected} like the rest of the set")]
+    GroupSize {
+        prefix: String,
+        found: UVec2,
+        expected: UVec2,
+    },
+    #[error("no frames found in {0}, expected them named like prefix_0001.png")]
+    NoFrames(PathBuf),
+    #[error("{0}: no frames to pack")]
+    NoGroups(String),
+    #[error("{0}: every frame is fully transparent")]
+    Empty(String),
+    #[error("could not write sprite set manifest: {0}")]
+    Ron(#[from] ron::Error),
+}
+
+/// A packed sprite sheet written to disk.
+#[derive(Debug)]
+pub struct PackedSheet {
+    pub prefix: String,
+    pub path: PathBuf,
+    pub sprites: UVec2,
+    pub size: UVec2,
+    /// Size of the original frames, before trimming.
+    pub source_size: UVec2,
+    /// Area of the original frames kept after trimming transparent borders.
+    pub trimmed: URect,
+}
+
+/// Groups `prefix_0001.png` style frames by prefix, each group sorted by frame number
+/// so unpadded numbers like `prefix_2.png` and `prefix_10.png` stay in order.
+pub fn group_frames(in_dir: &Path) -> Result<BTreeMap<String, Vec<PathBuf>>, PackError> {
+    let mut numbered: BTreeMap<String, Vec<(u32, PathBuf)>> = BTreeMap::new();
+
+    let entries = std::fs::read_dir(in_dir).map_err(|e| PackError::Io(in_dir.to_path_buf(), e))?;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+        let Some((prefix, number)) = name.strip_suffix(".png").and_then(|s| s.rsplit_once('_')) else {
+            continue;
+        };
+        if number.chars().all(|c| c.is_ascii_digit())
+            && let Ok(number) = number.parse()
+        {
+            numbered.entry(prefix.to_string()).or_default().push((number, path));
+        }
+    }
+    if numbered.is_empty() {
+        return Err(PackError::NoFrames(in_dir.to_path_buf()));
+    }
+
+    let grouped = numbered
+        .into_iter()
+        .map(|(prefix, mut frames)| {
+            frames.sort();
+            (prefix, frames.into_iter().map(|(_, path)| path).collect())
+        })
+        .collect();
+
+    Ok(grouped)
+}
+
+/// Picks the columns x rows grid with the fewest empty cells, preferring the squarest one.
+pub fn calculate_grid(frames: u32) -> UVec2 {
+    let mut best = UVec2::new(frames.max(1), 1);
+    let mut best_empty = u32::MAX;
+    let mut best_diff = u32::MAX;
+
+    for cols in 1..=frames {
+        let rows = frames.div_ceil(cols);
+        let empty = cols * rows - frames;
+        let diff = cols.abs_diff(rows);
+
+        if empty < best_empty || (empty == best_empty && diff < best_diff) {
+            best = UVec2::new(cols, rows);
+            best_empty = empty;
+            best_diff = diff;
+        }
+    }
+
+    best
+}
+
+/// File name of a packed sheet, "prefix_CxR_WxH.png".
+pub fn sheet_file_name(prefix: &str, sprites: UVec2, size: UVec2) -> String {
+    format!("{}_{}x{}_{}x{}.png", prefix, sprites.x, sprites.y, size.x, size.y)
+}
+
+/// Splits a "prefix_CxR_WxH.png" file name back into its prefix, grid and frame size.
+pub fn parse_sheet_file_name(name: &str) -> Option<(&str, UVec2, UVec2)> {
+    let mut parts = name.strip_suffix(".png")?.rsplitn(3, '_');
+    let size = parse_pair(parts.next()?)?;
+    let sprites = parse_pair(parts.next()?)?;
+    let prefix = parts.next()?;
+
+    Some((prefix, sprites, size))
+}
+
+fn parse_pair(s: &str) -> Option<UVec2> {
+    let (x, y) = s.split_once('x')?;
+    Some(UVec2::new(x.parse().ok()?, y.parse().ok()?))
+}
+
+/// Smallest rect holding every non transparent pixel of all frames, so the frames stay aligned.
+fn opaque_bounds(frames: &[RgbaImage]) -> Option<URect> {
+    let mut bounds: Option<URect> = None;
+
+    for frame in frames {
+        for (x, y, pixel) in frame.enumerate_pixels() {
+            if pixel[3] == 0 {
+                continue;
+            }
+            let point = URect::new(x, y, x + 1, y + 1);
+            bounds = Some(bounds.map_or(point, |b| b.union(point)));
+        }
+    }
+
+    bounds
+}
+
+/// Loads one group of frames, which must all be the size of the first.
+pub fn load_frames(prefix: &str, frame_paths: &[PathBuf]) -> Result<Vec<RgbaImage>, PackError> {
+    let mut frames = Vec::with_capacity(frame_paths.len());
+    for path in frame_paths {
+        let frame = image::open(path).map_err(|e| PackError::Image(path.clone(), e))?;
+        frames.push(frame.to_rgba8());
+    }
+
+    let expected = UVec2::from(frames[0].dimensions());
+    for (frame, path) in frames.iter().zip(frame_paths) {
+        let found = UVec2::from(frame.dimensions());
+        if found != expected {
+            return Err(PackError::FrameSize {
+                prefix: prefix.to_string(),
+                frame: path.clone(),
+                found,
+                expected,
+            });
+        }
+    }
+
+    Ok(frames)
+}
+
+/// Area of the frames every group of the set keeps, the opaque bounds of all of them when trimming.
+/// One crop for the whole set keeps the sheets aligned with each other and the same frame size.
+pub fn set_bounds(name: &str, groups: &[(String, Vec<RgbaImage>)], trim: bool) -> Result<URect, PackError> {
+    let Some((_, first)) = groups.first() else {
+        return Err(PackError::NoGroups(name.to_string()));
+    };
+    let expected = UVec2::from(first[0].dimensions());
+    for (prefix, frames) in groups {
+        let found = UVec2::from(frames[0].dimensions());
+        if found != expected {
+            return Err(PackError::GroupSize {
+                prefix: prefix.clone(),
+                found,
+                expected,
+            });
+        }
+    }
+
+    if !trim {
+        return Ok(URect::from_corners(UVec2::ZERO, expected));
+    }
+    let all: Vec<RgbaImage> = groups.iter().flat_map(|(_, frames)| frames.iter().cloned()).collect();
+    opaque_bounds(&all).ok_or_else(|| PackError::Empty(name.to_string()))
+}
+
+/// Packs one group of frames, cropped to `trimmed`, into a single sheet in `out_dir`.
+pub fn pack_group(
+    prefix: &str,
+    frames: &[RgbaImage],
+    trimmed: URect,
+    out_dir: &Path,
+) -> Result<PackedSheet, PackError> {
+    let size = trimmed.size();
+    let sprites = calculate_grid(frames.len() as u32);
+
+    let mut sheet = RgbaImage::new(sprites.x * size.x, sprites.y * size.y);
+    for (index, frame) in frames.iter().enumerate() {
+        let index = index as u32;
+        let cell = imageops::crop_imm(frame, trimmed.min.x, trimmed.min.y, size.x, size.y);
+        let x = (index % sprites.x) * size.x;
+        let y = (index / sprites.x) * size.y;
+        imageops::replace(&mut sheet, &cell.to_image(), x as i64, y as i64);
+    }
+
+    let path = out_dir.join(sheet_file_name(prefix, sprites, size));
+    sheet.save(&path).map_err(|e| PackError::Image(path.clone(), e))?;
+
+    Ok(PackedSheet {
+        prefix: prefix.to_string(),
+        path,
+        sprites,
+        size,
+        source_size: UVec2::from(frames[0].dimensions()),
+        trimmed,
+    })
+}
+
+/// Manifest entry for a packed sheet whose prefix is "<direction>_<state>".
+pub fn sheet_manifest(sheet: &PackedSheet) -> Option<SheetManifest> {
+    let (direction, state) = sheet.prefix.split_once('_')?;
+
+    Some(SheetManifest {
+        direction: Direction16::from_str(direction)?,
+        state: SpriteState::from_str(state)?,
+        path: sheet.path.file_name()?.to_str()?.to_string(),
+        sprites: sheet.sprites,
+        size: sheet.size,
+        padding: None,
+        offset: None,
+        frame_time: DEFAULT_FRAME_TIME,
+        frame_rate: None,
+        frame_durations: Vec::new(),
+        playback: PlaybackMode::default(),
+        events: Vec::new(),
+        anchor: None,
+        scale: None,
+        ground_speed: None,
+    })
+}
+
+/// Writes "<name>.spriteset.ron" into `out_dir` listing every sheet that maps onto a direction and state.
+/// When the sheets were trimmed the anchor is the centre of the original frames, so characters
+/// stand where they did before trimming.
+pub fn write_manifest(out_dir: &Path, name: &str, sheets: &[PackedSheet]) -> Result<PathBuf, PackError> {
+    let anchor = sheets
+        .first()
+        .filter(|sheet| sheet.trimmed.size() != sheet.source_size)
+        .map(|sheet| sheet.source_size.as_vec2() / 2.0 - sheet.trimmed.min.as_vec2());
+
+    let sheets: Vec<SheetManifest> = sheets.iter().filter_map(sheet_manifest).collect();
+    let manifest = SpriteSetManifest {
+        directions: DirectionResolution::smallest_containing(sheets.iter().map(|sheet| sheet.direction)),
+        pack: false,
+        anchor,
+        scale: 1.0,
+        mixed_sizes: false,
+        sheets,
+    };
+    let text = manifest_options().to_string_pretty(&manifest, ron::ser::PrettyConfig::default())?;
+
+    let path = out_dir.join(format!("{}.spriteset.ron", name));
+    std::fs::write(&path, text).map_err(|e| PackError::Io(path.clone(), e))?;
+
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    /// An empty directory under the system temp dir, unique to the test.
+    fn temp_dir(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("px-pack-{}-{}", test, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn file_names(paths: &[PathBuf]) -> Vec<&str> {
+        paths.iter().map(|path| path.file_name().unwrap().to_str().unwrap()).collect()
+    }
+
+    /// A `size` frame, transparent but for the `opaque` pixels.
+    fn frame(size: u32, opaque: &[(u32, u32)]) -> RgbaImage {
+        let mut frame = RgbaImage::new(size, size);
+        for &(x, y) in opaque {
+            frame.put_pixel(x, y, Rgba([255, 255, 255, 255]));
+        }
+        frame
+    }
+
+    #[test]
+    fn groups_sort_by_frame_number() {
+        let dir = temp_dir("sort");
+        for name in ["east_moving_10.png", "east_moving_2.png", "east_moving_1.png", "east_still_0001.png"] {
+            std::fs::write(dir.join(name), []).unwrap();
+        }
+        // not numbered frames
+        for name in ["east_moving_x.png", "east_moving_.png", "notes.txt", "east_moving_+3.png"] {
+            std::fs::write(dir.join(name), []).unwrap();
+        }
+
+        let groups = group_frames(&dir).unwrap();
+        assert_eq!(groups.keys().collect::<Vec<_>>(), ["east_moving", "east_still"]);
+        assert_eq!(
+            file_names(&groups["east_moving"]),
+            ["east_moving_1.png", "east_moving_2.png", "east_moving_10.png"]
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn no_frames_is_its_own_error() {
+        let dir = temp_dir("none");
+        std::fs::write(dir.join("readme.txt"), []).unwrap();
+
+        assert!(matches!(group_frames(&dir), Err(PackError::NoFrames(path)) if path == dir));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn bounds_cover_every_group() {
+        let groups = vec![
+            ("east_moving".to_string(), vec![frame(8, &[(2, 3)]), frame(8, &[(4, 1)])]),
+            ("east_still".to_string(), vec![frame(8, &[(6, 5)])]),
+        ];
+
+        assert_eq!(set_bounds("set", &groups, true).unwrap(), URect::new(2, 1, 7, 6));
+        assert_eq!(set_bounds("set", &groups, false).unwrap(), URect::new(0, 0, 8, 8));
+    }
+
+    #[test]
+    fn bounds_errors() {
+        assert!(matches!(set_bounds("set", &[], true), Err(PackError::NoGroups(_))));
+
+        let blank = vec![("east_still".to_string(), vec![frame(8, &[])])];
+        assert!(matches!(set_bounds("set", &blank, true), Err(PackError::Empty(_))));
+
+        let mixed = vec![
+            ("east_moving".to_string(), vec![frame(8, &[(0, 0)])]),
+            ("east_still".to_string(), vec![frame(4, &[(0, 0)])]),
+        ];
+        assert!(matches!(set_bounds("set", &mixed, true), Err(PackError::GroupSize { .. })));
+    }
+
+    #[test]
+    fn grid_is_squarest_without_gaps() {
+        assert_eq!(calculate_grid(1), UVec2::new(1, 1));
+        assert_eq!(calculate_grid(6), UVec2::new(2, 3));
+        assert_eq!(calculate_grid(7), UVec2::new(1, 7));
+        assert_eq!(calculate_grid(16), UVec2::new(4, 4));
+    }
+
+    #[test]
+    fn sheet_names_round_trip() {
+        let name = sheet_file_name("east_moving", UVec2::new(4, 2), UVec2::new(32, 48));
+        assert_eq!(name, "east_moving_4x2_32x48.png");
+        assert_eq!(parse_sheet_file_name(&name), Some(("east_moving", UVec2::new(4, 2), UVec2::new(32, 48))));
+        assert_eq!(parse_sheet_file_name("east_moving.png"), None);
+    }
+}
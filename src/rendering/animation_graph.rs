@@ -0,0 +1,275 @@
+use bevy::asset::io::Reader;
+use bevy::asset::{AssetLoader, LoadContext};
+use bevy::platform::collections::HashMap;
+use bevy::prelude::*;
+use serde::Deserialize;
+use thiserror::Error;
+use crate::game::action_buffer::{Action, ActionBuffer};
+use crate::game::character_state::CharacterState;
+use crate::rendering::sprite_state::{AnimationIndices, SpriteState};
+
+/// Which clip a character shows and when it moves on to the next one, loaded from `*.animgraph.ron`.
+#[derive(Asset, TypePath, Debug, Deserialize)]
+pub struct AnimationGraph {
+    pub states: Vec<GraphState>,
+}
+
+/// A clip in the graph and the transitions leaving it.
+#[derive(Debug, Deserialize)]
+pub struct GraphState {
+    pub state: SpriteState,
+    /// When false, transitions are only checked once the clip has finished.
+    #[serde(default = "default_interruptible")]
+    pub interruptible: bool,
+    /// Checked in order, the first one whose conditions all hold is taken.
+    #[serde(default)]
+    pub transitions: Vec<Transition>,
+}
+
+fn default_interruptible() -> bool {
+    true
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Transition {
+    pub to: SpriteState,
+    #[serde(default)]
+    pub conditions: Vec<Condition>,
+}
+
+#[derive(Debug, Deserialize)]
+pub enum Condition {
+    /// The character is in this gameplay state.
+    Character(CharacterState),
+    /// The current clip has completed a cycle.
+    Finished,
+    /// The named parameter is above the threshold.
+    Above(String, f32),
+    /// The named parameter is below the threshold.
+    Below(String, f32),
+    /// A press of the action is buffered. Taking the transition uses the press up, so a press made
+    /// during a clip that can't be interrupted is acted on once the clip finishes, and only once.
+    /// Presses made during such a clip don't expire before it ends, however long it is.
+    Pressed(Action),
+}
+
+/// Everything a condition can look at for one character.
+pub struct GraphContext<'a> {
+    pub character: CharacterState,
+    pub finished: bool,
+    pub params: &'a AnimationParams,
+    pub actions: Option<&'a ActionBuffer>,
+}
+
+impl Condition {
+    pub fn holds(&self, context: &GraphContext) -> bool {
+        match self {
+            Condition::Character(state) => context.character == *state,
+            Condition::Finished => context.finished,
+            Condition::Above(name, threshold) => context.params.get(name) > *threshold,
+            Condition::Below(name, threshold) => context.params.get(name) < *threshold,
+            Condition::Pressed(action) => context.actions.is_some_and(|actions| actions.get(*action).buffered()),
+        }
+    }
+}
+
+impl AnimationGraph {
+    /// The transition taken from `current`, if any fires.
+    pub fn next(&self, current: SpriteState, context: &GraphContext) -> Option<&Transition> {
+        let node = self.states.iter().find(|node| node.state == current)?;
+
+        if !node.interruptible && !context.finished {
+            return None;
+        }
+
+        node.transitions
+            .iter()
+            .find(|transition| transition.conditions.iter().all(|c| c.holds(context)))
+    }
+
+    /// Whether `state` is a clip that can't be interrupted, so buffered presses wait for it to end.
+    pub fn holds_presses(&self, state: SpriteState) -> bool {
+        self.states.iter().any(|node| node.state == state && !node.interruptible)
+    }
+}
+
+/// Named values gameplay systems hand to the animation graph, unset parameters read as 0.
+#[derive(Component, Debug, Default)]
+pub struct AnimationParams(pub HashMap<String, f32>);
+
+impl AnimationParams {
+    pub fn get(&self, name: &str) -> f32 {
+        self.0.get(name).copied().unwrap_or(0.0)
+    }
+
+    pub fn set(&mut self, name: &str, value: f32) {
+        self.0.insert(name.to_string(), value);
+    }
+}
+
+/// The animation graph a character's sprite state follows.
+#[derive(Component)]
+pub struct CharacterAnimation(pub Handle<AnimationGraph>);
+
+/// What a character's animation graph looks at.
+type GraphInputs = (
+    &'static CharacterState,
+    &'static AnimationParams,
+    &'static AnimationIndices,
+    Option<&'static mut ActionBuffer>,
+);
+
+pub fn evaluate_animation_graphs(
+    graphs: Res<Assets<AnimationGraph>>,
+    mut query: Query<(&CharacterAnimation, GraphInputs, &mut SpriteState)>,
+) {
+    for (animation, inputs, mut sprite) in query.iter_mut() {
+        let (state, params, indices, mut actions) = inputs;
+        let Some(graph) = graphs.get(&animation.0) else {
+            continue;
+        };
+
+        let context = GraphContext {
+            character: *state,
+            finished: indices.finished,
+            params,
+            actions: actions.as_deref(),
+        };
+        if let Some(transition) = graph.next(*sprite, &context) {
+            // the presses that let the transition through are used up
+            if let Some(actions) = &mut actions {
+                for condition in &transition.conditions {
+                    if let Condition::Pressed(action) = condition {
+                        actions.get_mut(*action).consume();
+                    }
+                }
+            }
+            if transition.to != *sprite {
+                *sprite = transition.to;
+            }
+        }
+
+        // presses made during a clip that can't be interrupted wait for it to end, then expire as usual
+        if let Some(actions) = &mut actions {
+            if indices.finished {
+                actions.release();
+            }
+            actions.hold = graph.holds_presses(*sprite);
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum AnimationGraphLoaderError {
+    #[error("could not read animation graph: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not parse animation graph: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+}
+
+/// Loads `*.animgraph.ron` files into `AnimationGraph` assets.
+#[derive(Default)]
+pub struct AnimationGraphLoader;
+
+impl AssetLoader for AnimationGraphLoader {
+    type Asset = AnimationGraph;
+    type Settings = ();
+    type Error = AnimationGraphLoaderError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<AnimationGraph, AnimationGraphLoaderError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+
+        Ok(ron::de::from_bytes(&bytes)?)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["animgraph.ron"]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+    use super::*;
+    use crate::game::action_buffer::DEFAULT_BUFFER_WINDOW;
+
+    /// A world with one still character in the Stopping clip of the game's own graph.
+    fn stopping_character() -> (World, Entity) {
+        let graph: AnimationGraph =
+            ron::de::from_str(include_str!("../../assets/animations/character.animgraph.ron")).unwrap();
+
+        let mut world = World::new();
+        let mut graphs = Assets::<AnimationGraph>::default();
+        let handle = graphs.add(graph);
+        world.insert_resource(graphs);
+
+        let entity = world
+            .spawn((
+                CharacterAnimation(handle),
+                CharacterState::Still,
+                AnimationParams::default(),
+                AnimationIndices::default(),
+                ActionBuffer::default(),
+                SpriteState::Stopping,
+            ))
+            .id();
+        (world, entity)
+    }
+
+    fn press(world: &mut World, entity: Entity) {
+        let mut buffer = world.get_mut::<ActionBuffer>(entity).unwrap();
+        let hold = buffer.hold;
+        buffer.action1.update(true, 0.0, hold);
+        buffer.action1.update(false, 0.0, hold);
+    }
+
+    fn step(world: &mut World, entity: Entity, finished: bool) -> SpriteState {
+        world.get_mut::<AnimationIndices>(entity).unwrap().finished = finished;
+        world.run_system_once(evaluate_animation_graphs).unwrap();
+        *world.get::<SpriteState>(entity).unwrap()
+    }
+
+    #[test]
+    fn presses_during_a_clip_fire_once_it_ends() {
+        let (mut world, entity) = stopping_character();
+        assert_eq!(step(&mut world, entity, false), SpriteState::Stopping);
+        assert!(world.get::<ActionBuffer>(entity).unwrap().hold);
+
+        press(&mut world, entity);
+        assert_eq!(step(&mut world, entity, false), SpriteState::Stopping);
+        assert!(world.get::<ActionBuffer>(entity).unwrap().action1.buffered());
+
+        // the clip ends and the press is used up taking the transition
+        assert_eq!(step(&mut world, entity, true), SpriteState::Starting);
+        assert!(!world.get::<ActionBuffer>(entity).unwrap().action1.buffered());
+    }
+
+    #[test]
+    fn without_a_press_the_clip_ends_as_usual() {
+        let (mut world, entity) = stopping_character();
+        assert_eq!(step(&mut world, entity, true), SpriteState::Still);
+        assert!(!world.get::<ActionBuffer>(entity).unwrap().hold);
+    }
+
+    #[test]
+    fn unused_presses_are_released_when_the_clip_ends() {
+        let (mut world, entity) = stopping_character();
+        *world.get_mut::<SpriteState>(entity).unwrap() = SpriteState::Moving;
+        *world.get_mut::<CharacterState>(entity).unwrap() = CharacterState::Moving;
+        step(&mut world, entity, false);
+
+        press(&mut world, entity);
+        assert_eq!(step(&mut world, entity, true), SpriteState::Moving);
+
+        // moving has no use for the press, so it expires like any other
+        let mut buffer = world.get_mut::<ActionBuffer>(entity).unwrap();
+        buffer.action1.update(false, DEFAULT_BUFFER_WINDOW, true);
+        assert!(!buffer.action1.buffered());
+    }
+}
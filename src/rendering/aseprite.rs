@@ -0,0 +1,381 @@
+use std::path::Path;
+use std::sync::Arc;
+use bevy::asset::io::Reader;
+use bevy::asset::{AssetLoader, LoadContext};
+use bevy::prelude::*;
+use serde::Deserialize;
+use thiserror::Error;
+use crate::direction::{Direction16, DirectionResolution};
+use crate::rendering::sprite_set::{Grid, SheetFrames, SpriteSet, SpriteSheet};
+use crate::rendering::sprite_state::{PlaybackMode, SpriteState};
+
+/// The JSON Aseprite writes next to an exported sprite sheet PNG.
+#[derive(Debug, Deserialize)]
+pub struct AsepriteExport {
+    pub frames: AsepriteFrames,
+    pub meta: AsepriteMeta,
+}
+
+/// Aseprite writes frames as an array or as a map keyed by frame file name.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum AsepriteFrames {
+    Array(Vec<AsepriteFrame>),
+    Hash(serde_json::Map<String, serde_json::Value>),
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AsepriteFrame {
+    pub frame: AsepriteRect,
+    /// The frame was cropped to its opaque pixels, so `frame` is smaller than `source_size`.
+    #[serde(default)]
+    pub trimmed: bool,
+    #[serde(rename = "sourceSize")]
+    pub source_size: AsepriteSize,
+    /// Milliseconds.
+    pub duration: u32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AsepriteRect {
+    pub x: u32,
+    pub y: u32,
+    pub w: u32,
+    pub h: u32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AsepriteSize {
+    pub w: u32,
+    pub h: u32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AsepriteMeta {
+    pub image: String,
+    pub size: AsepriteSize,
+    #[serde(rename = "frameTags", default)]
+    pub frame_tags: Vec<AsepriteTag>,
+    #[serde(default)]
+    pub slices: Vec<AsepriteSlice>,
+}
+
+/// A slice drawn in Aseprite, one with a pivot sets the anchor of the whole set.
+#[derive(Debug, Deserialize)]
+pub struct AsepriteSlice {
+    pub name: String,
+    pub keys: Vec<AsepriteSliceKey>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AsepriteSliceKey {
+    pub bounds: AsepriteRect,
+    #[serde(default)]
+    pub pivot: Option<AsepritePoint>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AsepritePoint {
+    pub x: u32,
+    pub y: u32,
+}
+
+/// A tagged frame range, named "<direction>_<state>" such as "east_moving".
+#[derive(Debug, Deserialize)]
+pub struct AsepriteTag {
+    pub name: String,
+    pub from: usize,
+    pub to: usize,
+    #[serde(default)]
+    pub direction: String,
+    #[serde(default)]
+    pub repeat: Option<String>,
+}
+
+impl AsepriteFrames {
+    pub fn into_vec(self) -> Result<Vec<AsepriteFrame>, serde_json::Error> {
+        match self {
+            AsepriteFrames::Array(frames) => Ok(frames),
+            AsepriteFrames::Hash(frames) => frames
+                .into_iter()
+                .map(|(_, frame)| serde_json::from_value(frame))
+                .collect(),
+        }
+    }
+}
+
+impl AsepriteMeta {
+    /// Pixel of the first slice pivot, pivots are relative to their slice's bounds.
+    pub fn anchor(&self) -> Option<Vec2> {
+        self.slices.iter().flat_map(|slice| &slice.keys).find_map(|key| {
+            let pivot = key.pivot.as_ref()?;
+            Some(Vec2::new((key.bounds.x + pivot.x) as f32, (key.bounds.y + pivot.y) as f32))
+        })
+    }
+}
+
+impl AsepriteTag {
+    /// Direction and state the tag's name maps onto.
+    pub fn sheet_key(&self) -> Option<(Direction16, SpriteState)> {
+        let (direction, state) = self.name.split_once('_')?;
+        Some((Direction16::from_str(direction)?, SpriteState::from_str(state)?))
+    }
+
+    /// Aseprite's "reverse" and "pingpong_reverse" start from the tag's last frame.
+    pub fn reversed(&self) -> bool {
+        self.direction.ends_with("reverse")
+    }
+
+    pub fn playback(&self) -> PlaybackMode {
+        match self.direction.as_str() {
+            "pingpong" | "pingpong_reverse" => PlaybackMode::PingPong,
+            // a tag set to play once holds its last frame, like our transition clips
+            _ if self.repeat.as_deref() == Some("1") => PlaybackMode::Clamp,
+            _ => PlaybackMode::Loop,
+        }
+    }
+}
+
+/// Builds a sprite set with one sheet per recognised tag, all cut from the export's single image.
+/// Reversed tags play copies of their frames in reverse order, added after the export's own.
+pub fn sprite_set_from_aseprite(
+    meta: &AsepriteMeta,
+    frames: &[AsepriteFrame],
+    image: Handle<Image>,
+    path: &Path,
+) -> Result<SpriteSet, AsepriteLoaderError> {
+    // a trimmed frame would be drawn smaller than the rest and off centre
+    if let Some(index) = frames.iter().position(|f| f.trimmed) {
+        return Err(AsepriteLoaderError::Trimmed(index));
+    }
+
+    let mut rects: Vec<URect> = frames
+        .iter()
+        .map(|f| URect::new(f.frame.x, f.frame.y, f.frame.x + f.frame.w, f.frame.y + f.frame.h))
+        .collect();
+
+    let mut clips = Vec::new();
+    for tag in &meta.frame_tags {
+        let Some((direction, state)) = tag.sheet_key() else {
+            warn!("{}: tag {} is not <direction>_<state>, skipped", path.display(), tag.name);
+            continue;
+        };
+        if tag.from > tag.to || tag.to >= frames.len() {
+            warn!("{}: tag {} has no frames {}..={}", path.display(), tag.name, tag.from, tag.to);
+            continue;
+        }
+
+        let mut durations: Vec<f32> = frames[tag.from..=tag.to]
+            .iter()
+            .map(|f| f.duration as f32 / 1000.0)
+            .collect();
+        let (first, last) = if tag.reversed() {
+            let first = rects.len();
+            rects.extend_from_within(tag.from..=tag.to);
+            rects[first..].reverse();
+            durations.reverse();
+            (first, rects.len() - 1)
+        } else {
+            (tag.from, tag.to)
+        };
+        clips.push((tag, direction, state, first, last, durations));
+    }
+
+    let rects: Arc<[URect]> = rects.into();
+    let anchor = meta.anchor();
+
+    let mut sheets = Vec::new();
+    for (tag, direction, state, first, last, durations) in clips {
+        let source_size = &frames[tag.from].source_size;
+        sheets.push(SpriteSheet {
+            image: image.clone(),
+            grid: Grid {
+                sprites: UVec2::new(durations.len() as u32, 1),
+                size: UVec2::new(source_size.w, source_size.h),
+                padding: None,
+                offset: None,
+                direction,
+                state,
+            },
+            frames: SheetFrames::Rects {
+                image_size: UVec2::new(meta.size.w, meta.size.h),
+                rects: rects.clone(),
+                first,
+                last,
+            },
+            frame_durations: durations.into(),
+            playback: tag.playback(),
+            events: Arc::new([]),
+            flip_x: false,
+            anchor,
+            scale: 1.0,
+            ground_speed: None,
+        });
+    }
+
+    let mut set = SpriteSet {
+        resolution: DirectionResolution::smallest_containing(
+            sheets.iter().map(|sheet| sheet.grid.direction),
+        ),
+        sheets,
+        mirrored: Vec::new(),
+        sped_up: Vec::new(),
+    };
+    set.mirror_missing();
+    set.fall_back_running();
+    Ok(set)
+}
+
+#[derive(Debug, Error)]
+pub enum AsepriteLoaderError {
+    #[error("could not read Aseprite export: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not parse Aseprite export: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("invalid image path in Aseprite export: {0}")]
+    Path(#[from] bevy::asset::ParseAssetPathError),
+    #[error("Aseprite export has trimmed frames, starting with frame {0}, export it without trimming")]
+    Trimmed(usize),
+}
+
+/// Loads Aseprite `*.aseprite.json` exports into `SpriteSet` assets.
+#[derive(Default)]
+pub struct AsepriteLoader;
+
+impl AssetLoader for AsepriteLoader {
+    type Asset = SpriteSet;
+    type Settings = ();
+    type Error = AsepriteLoaderError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        load_context: &mut LoadContext<'_>,
+    ) -> Result<SpriteSet, AsepriteLoaderError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let export: AsepriteExport = serde_json::from_slice(&bytes)?;
+        let frames = export.frames.into_vec()?;
+
+        let image_path = load_context.asset_path().resolve_embed(&export.meta.image)?;
+        let image = load_context.load(image_path);
+
+        let set = sprite_set_from_aseprite(&export.meta, &frames, image, load_context.path())?;
+        set.log_report(load_context.path());
+
+        Ok(set)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["aseprite.json"]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An export of `frames` 16x16 frames in a row, the `n`th held for `n + 1` tenths of a second.
+    fn export(frames: usize, tags: &str) -> AsepriteExport {
+        let frames: Vec<String> = (0..frames)
+            .map(|n| {
+                format!(
+                    r#"{{"frame": {{"x": {}, "y": 0, "w": 16, "h": 16}}, "sourceSize": {{"w": 16, "h": 16}}, "duration": {}}}"#,
+                    n * 16,
+                    (n + 1) * 100
+                )
+            })
+            .collect();
+        let json = format!(
+            r#"{{"frames": [{}], "meta": {{"image": "set.png", "size": {{"w": {}, "h": 16}}, "frameTags": [{}]}}}}"#,
+            frames.join(", "),
+            frames.len() * 16,
+            tags
+        );
+        serde_json::from_str(&json).unwrap()
+    }
+
+    fn convert(export: AsepriteExport) -> Result<SpriteSet, AsepriteLoaderError> {
+        let frames = export.frames.into_vec().unwrap();
+        sprite_set_from_aseprite(&export.meta, &frames, Handle::default(), Path::new("set.aseprite.json"))
+    }
+
+    /// Left edges of the frames a sheet plays, in order.
+    fn played(sheet: &SpriteSheet) -> Vec<u32> {
+        let SheetFrames::Rects { rects, first, last, .. } = &sheet.frames else {
+            panic!("aseprite sheets are cut from rects");
+        };
+        rects[*first..=*last].iter().map(|rect| rect.min.x).collect()
+    }
+
+    #[test]
+    fn tags_become_sheets() {
+        let set = convert(export(
+            5,
+            r#"{"name": "east_moving", "from": 0, "to": 2, "direction": "forward"},
+               {"name": "east_stopping", "from": 3, "to": 4, "direction": "forward", "repeat": "1"},
+               {"name": "idle", "from": 0, "to": 0, "direction": "forward"},
+               {"name": "east_still", "from": 4, "to": 9, "direction": "forward"}"#,
+        ))
+        .unwrap();
+
+        let moving = set.get(Direction16::East, SpriteState::Moving).unwrap();
+        assert_eq!(played(moving), [0, 16, 32]);
+        assert_eq!(moving.grid.sprites, UVec2::new(3, 1));
+        assert_eq!(moving.grid.size, UVec2::new(16, 16));
+        assert_eq!(&*moving.frame_durations, [0.1, 0.2, 0.3]);
+        assert_eq!(moving.playback, PlaybackMode::Loop);
+
+        let stopping = set.get(Direction16::East, SpriteState::Stopping).unwrap();
+        assert_eq!(played(stopping), [48, 64]);
+        assert_eq!(stopping.playback, PlaybackMode::Clamp);
+
+        // the misnamed tag and the one past the last frame are skipped
+        assert!(set.get(Direction16::East, SpriteState::Still).is_none());
+    }
+
+    #[test]
+    fn reversed_tags_play_backwards() {
+        let set = convert(export(
+            4,
+            r#"{"name": "east_moving", "from": 0, "to": 2, "direction": "reverse"},
+               {"name": "east_still", "from": 1, "to": 3, "direction": "pingpong_reverse"}"#,
+        ))
+        .unwrap();
+
+        let moving = set.get(Direction16::East, SpriteState::Moving).unwrap();
+        assert_eq!(played(moving), [32, 16, 0]);
+        assert_eq!(&*moving.frame_durations, [0.3, 0.2, 0.1]);
+        assert_eq!(moving.playback, PlaybackMode::Loop);
+
+        let still = set.get(Direction16::East, SpriteState::Still).unwrap();
+        assert_eq!(played(still), [48, 32, 16]);
+        assert_eq!(still.playback, PlaybackMode::PingPong);
+    }
+
+    #[test]
+    fn trimmed_frames_are_rejected() {
+        let mut export = export(3, r#"{"name": "east_moving", "from": 0, "to": 2}"#);
+        if let AsepriteFrames::Array(frames) = &mut export.frames {
+            frames[1].trimmed = true;
+        }
+
+        assert!(matches!(convert(export), Err(AsepriteLoaderError::Trimmed(1))));
+    }
+
+    #[test]
+    fn hash_frames_keep_their_order() {
+        let json = r#"{
+            "frames": {
+                "set 1.png": {"frame": {"x": 16, "y": 0, "w": 16, "h": 16}, "sourceSize": {"w": 16, "h": 16}, "duration": 100},
+                "set 0.png": {"frame": {"x": 0, "y": 0, "w": 16, "h": 16}, "sourceSize": {"w": 16, "h": 16}, "duration": 100}
+            },
+            "meta": {"image": "set.png", "size": {"w": 32, "h": 16}}
+        }"#;
+        let export: AsepriteExport = serde_json::from_str(json).unwrap();
+
+        let frames = export.frames.into_vec().unwrap();
+        assert_eq!(frames.iter().map(|f| f.frame.x).collect::<Vec<_>>(), [16, 0]);
+    }
+}
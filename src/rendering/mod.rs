@@ -1,3 +1,7 @@
 pub mod sprite_render;
 pub mod sprite_state;
-pub mod sprite_set;
\ No newline at end of file
+pub mod sprite_set;
+pub mod sprite_library;
+pub mod animation_graph;
+pub mod aseprite;
+pub mod y_sort;
@@ -0,0 +1,153 @@
+use std::sync::Arc;
+use bevy::platform::collections::HashMap;
+use bevy::prelude::*;
+use crate::direction::{Direction16, DirectionResolution};
+use crate::rendering::sprite_set::{SheetFrames, SpriteSet, SpriteSheet, sprite_set_path};
+use crate::rendering::sprite_state::SpriteState;
+
+/// A sheet of a sprite set together with its atlas layout, shared by every character using the set.
+#[derive(Debug, Clone)]
+pub struct SharedSheet {
+    pub sheet: SpriteSheet,
+    pub layout: Handle<TextureAtlasLayout>,
+}
+
+/// Lookup table from (facing, SpriteState) to the shared sheet drawn for it.
+#[derive(Debug, Default)]
+pub struct SpriteTable {
+    pub resolution: DirectionResolution,
+    sheets: HashMap<(Direction16, SpriteState), SharedSheet>,
+}
+
+impl SpriteTable {
+    pub fn get(&self, direction: Direction16, state: SpriteState) -> Option<&SharedSheet> {
+        self.sheets.get(&(direction, state))
+    }
+
+    /// Seconds one pass through a state's clip lasts, the longest of its facings.
+    pub fn clip_duration(&self, state: SpriteState) -> Option<f32> {
+        self.sheets
+            .values()
+            .filter(|shared| shared.sheet.grid.state == state)
+            .map(|shared| shared.sheet.frame_durations.iter().sum::<f32>())
+            .reduce(f32::max)
+    }
+}
+
+struct LibraryEntry {
+    set: Handle<SpriteSet>,
+    table: Option<SpriteTable>,
+}
+
+/// Loads each named sprite set once and builds its atlas layouts once,
+/// so characters only clone handles instead of creating their own layouts.
+#[derive(Resource, Default)]
+pub struct SpriteSetLibrary {
+    sets: HashMap<String, LibraryEntry>,
+}
+
+impl SpriteSetLibrary {
+    /// Starts loading the sprite set manifest under `assets/textures/<name>` if it isn't known yet.
+    pub fn load(&mut self, name: &str, asset_server: &AssetServer) -> Handle<SpriteSet> {
+        self.load_path(name, &sprite_set_path(name), asset_server)
+    }
+
+    /// Starts loading a sprite set from any supported file, such as an Aseprite export.
+    pub fn load_path(&mut self, name: &str, path: &str, asset_server: &AssetServer) -> Handle<SpriteSet> {
+        self.sets
+            .entry(name.to_string())
+            .or_insert_with(|| LibraryEntry {
+                set: asset_server.load(path.to_string()),
+                table: None,
+            })
+            .set
+            .clone()
+    }
+
+    /// Lookup table of a sprite set, or `None` while it is still loading.
+    /// Layouts are built the first time a loaded set is requested.
+    pub fn table(
+        &mut self,
+        name: &str,
+        sprite_sets: &Assets<SpriteSet>,
+        texture_atlas_layouts: &mut Assets<TextureAtlasLayout>,
+    ) -> Option<&SpriteTable> {
+        let entry = self.sets.get_mut(name)?;
+
+        if entry.table.is_none() {
+            let set = sprite_sets.get(&entry.set)?;
+            entry.table = Some(build_table(set, texture_atlas_layouts));
+        }
+
+        entry.table.as_ref()
+    }
+
+    /// Drops the tables built from a sprite set asset that changed, returning the names using it.
+    /// The next call to `table` rebuilds them from the reloaded set.
+    pub fn invalidate(&mut self, id: AssetId<SpriteSet>) -> Vec<String> {
+        self.sets
+            .iter_mut()
+            .filter(|(_, entry)| entry.set.id() == id)
+            .map(|(name, entry)| {
+                entry.table = None;
+                name.clone()
+            })
+            .collect()
+    }
+
+    /// Lookup table of a sprite set that has already been built by `table`.
+    pub fn get(&self, name: &str) -> Option<&SpriteTable> {
+        self.sets.get(name)?.table.as_ref()
+    }
+}
+
+fn build_table(
+    set: &SpriteSet,
+    texture_atlas_layouts: &mut Assets<TextureAtlasLayout>,
+) -> SpriteTable {
+    // sheets cut from one shared set of rects also share one layout
+    let mut rect_layouts: HashMap<*const [URect], Handle<TextureAtlasLayout>> = HashMap::default();
+
+    let shared_sheets = set
+        .sheets
+        .iter()
+        .map(|sheet| {
+            let grid = &sheet.grid;
+            let layout = match &sheet.frames {
+                SheetFrames::Grid => texture_atlas_layouts.add(TextureAtlasLayout::from_grid(
+                    grid.size,
+                    grid.sprites[0],
+                    grid.sprites[1],
+                    grid.padding,
+                    grid.offset,
+                )),
+                SheetFrames::Rects { image_size, rects, .. } => rect_layouts
+                    .entry(Arc::as_ptr(rects))
+                    .or_insert_with(|| {
+                        let mut layout = TextureAtlasLayout::new_empty(*image_size);
+                        for rect in rects.iter() {
+                            layout.add_texture(*rect);
+                        }
+                        texture_atlas_layouts.add(layout)
+                    })
+                    .clone(),
+            };
+
+            let shared = SharedSheet {
+                sheet: sheet.clone(),
+                layout,
+            };
+            ((grid.direction, grid.state), shared)
+        });
+
+    // a duplicated (facing, state) draws the first sheet listed for it, as px-validate reports
+    let mut sheets = HashMap::default();
+    for (key, shared) in shared_sheets {
+        sheets.entry(key).or_insert(shared);
+    }
+
+    SpriteTable {
+        resolution: set.resolution,
+        sheets,
+    }
+}
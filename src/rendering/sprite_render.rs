@@ -1,97 +1,140 @@
-use crate::direction::Direction8;
+use crate::rendering::sprite_library::SpriteSetLibrary;
+use crate::rendering::sprite_state::{ShownSheet, SpriteBundle, SpriteState};
+use crate::rendering::sprite_state::{AnimationEvent, AnimationIndices, AnimationTimer};
+use crate::rendering::sprite_state::{ClipGroundSpeed, FrameDurations, FrameEvents};
 use crate::game::character_state::CharacterState;
-use crate::rendering::sprite_state::SpriteState;
-use crate::rendering::sprite_state::{AnimationIndices, AnimationTimer};
+use crate::game::movement::{Facing, GroundSpeed};
+use crate::spawn::{CharacterSprites, PendingSprites};
 use bevy::prelude::*;
 
 pub fn setup_camera(mut commands: Commands) {
-    commands.spawn(Camera2d::default());
+    commands.spawn(Camera2d);
 }
 
+/// Components of a character's single animated sprite.
+type CharacterSprite = (
+    &'static mut ShownSheet,
+    &'static mut Sprite,
+    &'static mut AnimationIndices,
+    &'static mut AnimationTimer,
+    &'static mut FrameDurations,
+    &'static mut FrameEvents,
+    &'static mut ClipGroundSpeed,
+    &'static mut Visibility,
+);
+
 pub fn update_character_sprites(
-    mut char_query: Query<(
-        &CharacterState,
-        &Direction8,
-        &mut SpriteState,
-        &mut Children,
-    )>,
-    mut sprite_query: Query<
+    library: Res<SpriteSetLibrary>,
+    mut animation_events: EventWriter<AnimationEvent>,
+    mut query: Query<
         (
+            Entity,
+            &Facing,
             &SpriteState,
-            &Direction8,
-            &AnimationIndices,
-            &mut Visibility,
+            &CharacterSprites,
+            CharacterSprite,
         ),
-        Without<CharacterState>,
+        Without<PendingSprites>,
     >,
 ) {
-    // change this to track last state and direction to avoid unnecessary updates
-    for (state, direction, mut sprite, children) in char_query.iter_mut() {
-        let mut can_change = false;
+    for (entity, facing, sprite, sprites, character_sprite) in query.iter_mut() {
+        let (
+            mut shown,
+            mut image,
+            mut indices,
+            mut timer,
+            mut durations,
+            mut events,
+            mut clip_speed,
+            mut visibility,
+        ) = character_sprite;
 
-        // Update child sprite visibilities
-        for child in children.iter() {
-            let (child_sprite_state, child_direction, indices, mut visibility) =
-                sprite_query.get_mut(child).unwrap();
+        let Some(table) = library.get(&sprites.0) else {
+            continue;
+        };
+        // the set may have fewer or more facings than Direction8
+        let facing = table
+            .resolution
+            .nearest(facing.0, shown.0.map(|(facing, _)| facing));
 
-            if *child_direction == *direction && *child_sprite_state == *sprite {
-                *visibility = Visibility::Visible;
+        // Swap the image and atlas only when the shown sheet changes
+        if shown.0 == Some((facing, *sprite)) {
+            continue;
+        }
+        let keep_frame = matches!(shown.0, Some((_, shown_state)) if shown_state == *sprite);
+        shown.0 = Some((facing, *sprite));
 
-                if indices.current == indices.last {
-                    can_change = true;
+        match table.get(facing, *sprite) {
+            Some(shared) => {
+                SpriteBundle::show(
+                    &mut image,
+                    &mut indices,
+                    &mut timer,
+                    &mut durations,
+                    &mut events,
+                    &mut clip_speed,
+                    shared,
+                    keep_frame,
+                );
+                *visibility = Visibility::Inherited;
+
+                // a fresh clip enters its first frame
+                if !keep_frame {
+                    events.fire(&mut animation_events, entity, *shown, indices.current - indices.first);
                 }
-            } else {
-                *visibility = Visibility::Hidden;
             }
-        }
-
-        // Update sprite state based on character state and direction
-        if can_change || *sprite == SpriteState::Still {
-            match state {
-                CharacterState::Still => match *sprite {
-                    SpriteState::Moving | SpriteState::Starting => {
-                        *sprite = SpriteState::Stopping;
-                    }
-                    _ => {
-                        *sprite = SpriteState::Still;
-                    }
-                },
-                CharacterState::Moving => match *sprite {
-                    SpriteState::Still | SpriteState::Stopping => {
-                        *sprite = SpriteState::Starting;
-                    }
-                    _ => {
-                        *sprite = SpriteState::Moving;
-                    }
-                },
+            None => {
+                *visibility = Visibility::Hidden;
             }
         }
     }
 }
 
+/// Playback state of an animated sprite.
+type AnimatedSprite = (
+    &'static mut AnimationIndices,
+    &'static mut AnimationTimer,
+    &'static FrameDurations,
+    &'static FrameEvents,
+    &'static ShownSheet,
+    &'static ClipGroundSpeed,
+);
+
+/// How fast and in what state the character is, for sprites that belong to one.
+type Pacing = Option<(&'static GroundSpeed, &'static CharacterState)>;
+
 pub fn animate_sprites(
     time: Res<Time>,
-    mut query: Query<(
-        &mut AnimationIndices,
-        &mut AnimationTimer,
-        &mut Sprite,
-        &Visibility,
-    )>,
+    mut animation_events: EventWriter<AnimationEvent>,
+    mut query: Query<(Entity, AnimatedSprite, &mut Sprite, &Visibility, Pacing)>,
 ) {
-    for (mut indices, mut timer, mut sprite, visibility) in query.iter_mut() {
-        if *visibility == Visibility::Hidden {
+    for (entity, animated, mut sprite, visibility, movement) in query.iter_mut() {
+        let (mut indices, mut timer, durations, events, shown, clip_speed) = animated;
+
+        if *visibility == Visibility::Hidden || indices.stopped() {
             continue;
         }
-        timer.tick(time.delta());
+        // clips drawn for a ground speed follow how fast the character really moves, but only while
+        // it means to move, a walk cycle left playing as it slows to a stop still runs to its end
+        let rate = match movement {
+            Some((speed, CharacterState::Moving | CharacterState::Running)) => clip_speed.rate(speed.0),
+            _ => 1.0,
+        };
+        timer.tick(time.delta().mul_f32(rate));
+
+        if timer.just_finished()
+            && let Some(atlas) = &mut sprite.texture_atlas
+        {
+            let previous = indices.current;
+            indices.advance();
+            atlas.index = indices.current;
+
+            // hold the new frame for its own duration
+            timer.set_duration(durations.get(indices.current - indices.first));
 
-        if timer.just_finished() {
-            if let Some(atlas) = &mut sprite.texture_atlas {
-                atlas.index = if atlas.index == indices.last {
-                    indices.first
-                } else {
-                    atlas.index + 1
-                };
-                indices.current = atlas.index;
+            // a clamped clip holding its last frame doesn't enter it again
+            if indices.current != previous || !indices.stopped() {
+                events.fire(&mut animation_events, entity, *shown, indices.current - indices.first);
             }
         }
     }
@@ -1,89 +1,538 @@
 use std::path::Path;
+use std::sync::Arc;
+use bevy::asset::io::Reader;
+use bevy::asset::{AssetLoader, LoadContext, LoadDirectError};
+use bevy::image::{TextureAtlasBuilder, TextureAtlasBuilderError};
 use bevy::prelude::*;
-use crate::rendering::sprite_state::SpriteState;
-use crate::direction::Direction8;
+use bevy::sprite::Anchor;
 use enum_iterator::all;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use crate::rendering::sprite_state::{PlaybackMode, SpriteState};
+use crate::direction::{Direction16, DirectionResolution};
 
+/// Default seconds per frame for sheets that don't declare a frame time.
+pub const DEFAULT_FRAME_TIME: f32 = 0.2;
 
-#[derive(Debug)]
+/// How much faster the moving clip plays when it stands in for missing running sheets,
+/// unless it declares a ground speed and follows the character's speed anyway.
+pub const RUNNING_FALLBACK_RATE: f32 = 1.5;
+
+/// Largest atlas a packed sprite set may grow to on either side, most GPUs support at least this.
+pub const ATLAS_MAX_SIZE: u32 = 8192;
+
+#[derive(Debug, Clone)]
 pub struct Grid {
     pub sprites: UVec2,
     pub size: UVec2,
-    pub direction: Direction8,
+    pub padding: Option<UVec2>,
+    pub offset: Option<UVec2>,
+    pub direction: Direction16,
     pub state: SpriteState,
 }
 
-/// Gets all available textures for a sprite name
-pub fn get_textures(name: &str) -> Vec<String> {
-    let mut textures = Vec::new();
+/// Where a sheet's frames sit in its image.
+#[derive(Debug, Clone)]
+pub enum SheetFrames {
+    /// Cut from the regular grid described by the sheet's `Grid`.
+    Grid,
+    /// Explicit rects in an image shared by several sheets, the sheet plays `first..=last` of them.
+    Rects {
+        image_size: UVec2,
+        rects: Arc<[URect]>,
+        first: usize,
+        last: usize,
+    },
+}
 
-    for direction in all::<Direction8>() {
-        for state in all::<SpriteState>() {
-            let base = format!("{}_{}", direction.as_str(), state.as_str());
-            if let Some(texture) = find_existing_texture(name, &base) {
-                textures.push(texture);
-            }
+/// One sheet of a sprite set, ready to be shown by a `SpriteBundle`.
+#[derive(Debug, Clone)]
+pub struct SpriteSheet {
+    pub image: Handle<Image>,
+    pub grid: Grid,
+    pub frames: SheetFrames,
+    /// Seconds each frame is held, one entry per frame of the clip.
+    pub frame_durations: Arc<[f32]>,
+    pub playback: PlaybackMode,
+    pub events: Arc<[FrameEvent]>,
+    /// Drawn flipped horizontally, the sheet was borrowed from the mirrored direction.
+    pub flip_x: bool,
+    /// Pixel of a frame, from its top left, that sits on the character's position,
+    /// such as where the feet touch the ground. `None` is the centre of the frame.
+    pub anchor: Option<Vec2>,
+    /// World units per pixel of the frame.
+    pub scale: f32,
+    /// World units per second the clip was drawn moving at, when set the clip plays faster
+    /// or slower to match how fast the character really moves so its feet don't slide.
+    pub ground_speed: Option<f32>,
+}
+
+impl SpriteSheet {
+    /// Atlas index of the clip's first frame.
+    pub fn first_frame(&self) -> usize {
+        match &self.frames {
+            SheetFrames::Grid => 0,
+            SheetFrames::Rects { first, .. } => *first,
+        }
+    }
+
+    /// Size the frames are drawn at in the world.
+    pub fn world_size(&self) -> Vec2 {
+        self.grid.size.as_vec2() * self.scale
+    }
+
+    /// The sprite anchor placing `anchor` on the character's position, following a horizontal flip.
+    pub fn sprite_anchor(&self) -> Anchor {
+        let Some(anchor) = self.anchor else {
+            return Anchor::Center;
+        };
+
+        let size = self.grid.size.as_vec2();
+        let mut offset = Vec2::new(anchor.x / size.x - 0.5, 0.5 - anchor.y / size.y);
+        if self.flip_x {
+            offset.x = -offset.x;
+        }
+        Anchor::Custom(offset)
+    }
+
+    /// Atlas index of the clip's last frame.
+    pub fn last_frame(&self) -> usize {
+        match &self.frames {
+            SheetFrames::Grid => (self.grid.sprites.x * self.grid.sprites.y) as usize - 1,
+            SheetFrames::Rects { last, .. } => *last,
         }
     }
+}
 
-    textures
+/// A named event fired when a sheet enters one of its frames, such as a footstep.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FrameEvent {
+    pub frame: usize,
+    pub name: String,
 }
 
-/// Checks if "base_NxM.png" exists.
-pub fn find_existing_texture(set: &str, base: &str) -> Option<String> {
-    let texture_dir = Path::new("assets");
+/// A named collection of sprite sheets, one per (facing, SpriteState) for the facings of its resolution.
+#[derive(Asset, TypePath, Debug)]
+pub struct SpriteSet {
+    pub resolution: DirectionResolution,
+    pub sheets: Vec<SpriteSheet>,
+    /// Sheets that were missing and are drawn by flipping the mirrored direction.
+    pub mirrored: Vec<(Direction16, SpriteState)>,
+    /// Facings without running sheets, they run with their moving clip sped up.
+    pub sped_up: Vec<Direction16>,
+}
 
-    // check for any "_NxM.png"
-    if let Ok(entries) = std::fs::read_dir(texture_dir.join(format!("textures/{}", set))) {
-        for entry in entries.flatten() {
-            let path = entry.path();
-            if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
-                if name.starts_with(&base) && name.ends_with(".png") {
-                    return Some(format!("textures/{}/{}", set, name));
+impl SpriteSet {
+    pub fn get(&self, direction: Direction16, state: SpriteState) -> Option<&SpriteSheet> {
+        self.sheets
+            .iter()
+            .find(|sheet| sheet.grid.direction == direction && sheet.grid.state == state)
+    }
+
+    /// Fills missing directions with a horizontally flipped copy of the mirrored direction,
+    /// so artists only need to draw one side of the character.
+    pub fn mirror_missing(&mut self) {
+        for direction in self.resolution.facings() {
+            for state in all::<SpriteState>() {
+                if self.get(direction, state).is_some() {
+                    continue;
+                }
+                let Some(source) = self.get(direction.mirrored(), state) else {
+                    continue;
+                };
+                if source.flip_x {
+                    continue;
                 }
+
+                let mut sheet = source.clone();
+                sheet.grid.direction = direction;
+                sheet.flip_x = true;
+                self.sheets.push(sheet);
+                self.mirrored.push((direction, state));
+            }
+        }
+    }
+
+    /// Fills missing running sheets with a sped up copy of the moving clip of the same facing.
+    pub fn fall_back_running(&mut self) {
+        for direction in self.resolution.facings() {
+            if self.get(direction, SpriteState::Running).is_some() {
+                continue;
+            }
+            let Some(moving) = self.get(direction, SpriteState::Moving) else {
+                continue;
+            };
+
+            let mut sheet = moving.clone();
+            sheet.grid.state = SpriteState::Running;
+            if sheet.ground_speed.is_none() {
+                sheet.frame_durations = sheet
+                    .frame_durations
+                    .iter()
+                    .map(|duration| duration / RUNNING_FALLBACK_RATE)
+                    .collect();
             }
+            self.sheets.push(sheet);
+            self.sped_up.push(direction);
         }
     }
-    return None;
-}
-
-pub fn parse_grid_from_filename(filename: &str) -> Option<Grid> {
-    // Files are named as "base_AxB_CxD.png"
-    // AxB is the number of sprites in the sheet, as in there are A columns and B rows of frames.
-    // CxD is the size of each sprite in the sheet, as in each sprite is C pixels wide and D pixels tall.
-
-    let stem = Path::new(filename).file_stem()?.to_str()?;
-
-    // Split into parts using '_'
-    let parts: Vec<&str> = stem.split('_').collect();
-
-    // Check if we have at least three parts (the grid and sprite size)
-    if parts.len() >= 3 {
-        let sprite_grid = parts[parts.len() - 2]; // 2x5 or similar
-        let sprite_size = parts[parts.len() - 1]; // 500x500 or similar
-        
-        if let Some(direction) = Direction8::from_str(parts[0]) {
-            if let Some(state) = SpriteState::from_str(parts[1]) {
-                // Parse the grid size (2x5 -> 2 columns, 5 rows)
-                if let Some((cols_str, rows_str)) = sprite_grid.split_once('x') {
-                    let cols = cols_str.parse().ok()?;
-                    let rows = rows_str.parse().ok()?;
-
-                    // Parse the sprite size (500x500 -> 500 width, 500 height)
-                    if let Some((width_str, height_str)) = sprite_size.split_once('x') {
-                        let width = width_str.parse().ok()?;
-                        let height = height_str.parse().ok()?;
-                        return Some(Grid {
-                            sprites: UVec2::new(cols, rows),
-                            size: UVec2::new(width, height),
-                            direction,
-                            state,
-                        });
-                    }
+
+    /// Logs mirrored sheets, sheets outside the set's resolution and missing sheets.
+    pub fn log_report(&self, path: &Path) {
+        if !self.mirrored.is_empty() {
+            let names: Vec<String> = self
+                .mirrored
+                .iter()
+                .map(|(direction, state)| format!("{}_{}", direction.as_str(), state.as_str()))
+                .collect();
+            info!("{}: mirrored {}", path.display(), names.join(", "));
+        }
+
+        if !self.sped_up.is_empty() {
+            let names: Vec<&str> = self.sped_up.iter().map(|direction| direction.as_str()).collect();
+            info!("{}: running with the moving clip for {}", path.display(), names.join(", "));
+        }
+
+        for sheet in &self.sheets {
+            if !self.resolution.contains(sheet.grid.direction) {
+                warn!(
+                    "{}: {}_{} is not one of the set's {} directions",
+                    path.display(),
+                    sheet.grid.direction.as_str(),
+                    sheet.grid.state.as_str(),
+                    self.resolution.count()
+                );
+            }
+        }
+
+        for direction in self.resolution.facings() {
+            for state in all::<SpriteState>() {
+                if self.get(direction, state).is_none() {
+                    warn!(
+                        "{}: no sheet for {}_{}",
+                        path.display(),
+                        direction.as_str(),
+                        state.as_str()
+                    );
                 }
             }
         }
     }
+}
+
+/// On disk layout of a `*.spriteset.ron` file.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SpriteSetManifest {
+    /// Facings the set is drawn with, defaults to 8.
+    #[serde(default)]
+    pub directions: DirectionResolution,
+    /// Packs every sheet into one atlas texture at load time, so the whole set is a single texture bind.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub pack: bool,
+    /// Pixel of every frame placed on the character's position, sheets can override it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub anchor: Option<Vec2>,
+    /// World units per pixel, sheets can override it.
+    #[serde(default = "default_scale")]
+    pub scale: f32,
+    /// The sheets are meant to have different frame sizes, which px-validate otherwise reports as an error.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub mixed_sizes: bool,
+    pub sheets: Vec<SheetManifest>,
+}
+
+fn default_scale() -> f32 {
+    1.0
+}
+
+/// A single sheet entry in a `*.spriteset.ron` file.
+/// `path` is relative to the manifest, `sprites` is columns x rows and `size` is the frame size in pixels.
+/// Frames are held for `frame_time` seconds, or `1 / frame_rate` when a rate is given,
+/// and `frame_durations` can override that for the first frames to hold key poses longer.
+/// `anchor` and `scale` override the set's own for this sheet, and `ground_speed` ties the
+/// playback rate to the character's speed.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SheetManifest {
+    pub direction: Direction16,
+    pub state: SpriteState,
+    pub path: String,
+    pub sprites: UVec2,
+    pub size: UVec2,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub padding: Option<UVec2>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offset: Option<UVec2>,
+    #[serde(default = "default_frame_time")]
+    pub frame_time: f32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub frame_rate: Option<f32>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub frame_durations: Vec<f32>,
+    #[serde(default)]
+    pub playback: PlaybackMode,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub events: Vec<FrameEvent>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub anchor: Option<Vec2>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scale: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ground_speed: Option<f32>,
+}
+
+fn default_frame_time() -> f32 {
+    DEFAULT_FRAME_TIME
+}
+
+impl SheetManifest {
+    pub fn grid(&self) -> Grid {
+        Grid {
+            sprites: self.sprites,
+            size: self.size,
+            padding: self.padding,
+            offset: self.offset,
+            direction: self.direction,
+            state: self.state,
+        }
+    }
+
+    /// Seconds each frame of the sheet is held.
+    pub fn frame_durations(&self) -> Vec<f32> {
+        let frames = (self.sprites.x * self.sprites.y) as usize;
+        let frame_time = self.frame_rate.map_or(self.frame_time, |rate| 1.0 / rate);
 
-    None
-}
\ No newline at end of file
+        (0..frames)
+            .map(|frame| self.frame_durations.get(frame).copied().unwrap_or(frame_time))
+            .collect()
+    }
+
+    /// What is wrong with the sheet's grid, a sheet needs at least one frame of at least one pixel.
+    pub fn grid_error(&self) -> Option<String> {
+        (self.sprites.x == 0 || self.sprites.y == 0 || self.size.x == 0 || self.size.y == 0).then(|| {
+            format!(
+                "empty grid {}x{} of {}x{} frames",
+                self.sprites.x, self.sprites.y, self.size.x, self.size.y
+            )
+        })
+    }
+
+    /// What is wrong with the sheet's timing, frames can't be held for zero, negative or endless time.
+    pub fn timing_error(&self) -> Option<String> {
+        let valid = |seconds: f32| seconds.is_finite() && seconds > 0.0;
+
+        match self.frame_rate {
+            Some(rate) if !valid(rate) => return Some(format!("frame_rate {} is not a positive number", rate)),
+            None if !valid(self.frame_time) => {
+                return Some(format!("frame_time {} is not a positive number", self.frame_time));
+            }
+            _ => {}
+        }
+        self.frame_durations
+            .iter()
+            .position(|&seconds| !valid(seconds))
+            .map(|frame| format!("frame_durations[{}] {} is not a positive number", frame, self.frame_durations[frame]))
+    }
+}
+
+/// RON options for manifests, `implicit_some` lets optional fields skip the `Some(..)`.
+pub fn manifest_options() -> ron::Options {
+    ron::Options::default().with_default_extension(ron::extensions::Extensions::IMPLICIT_SOME)
+}
+
+#[derive(Debug, Error)]
+pub enum SpriteSetLoaderError {
+    #[error("could not read sprite set manifest: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not parse sprite set manifest: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+    #[error("invalid sheet path in sprite set manifest: {0}")]
+    Path(#[from] bevy::asset::ParseAssetPathError),
+    #[error("could not load sheet to pack: {0}")]
+    Sheet(#[from] LoadDirectError),
+    #[error("could not pack sprite set atlas: {0}")]
+    Atlas(#[from] TextureAtlasBuilderError),
+    #[error("invalid grid for {0} sheet in sprite set manifest: {1}")]
+    Grid(String, String),
+    #[error("invalid timing for {0} sheet in sprite set manifest: {1}")]
+    Timing(String, String),
+}
+
+/// Loads every sheet of the manifest and packs them into one atlas image, returning the atlas
+/// and where each sheet's frames ended up. Frames keep their sheet order, so every clip is a
+/// contiguous index range of the shared rects.
+async fn pack_sheets(
+    manifest: &SpriteSetManifest,
+    load_context: &mut LoadContext<'_>,
+) -> Result<(Handle<Image>, Vec<SheetFrames>), SpriteSetLoaderError> {
+    let mut images = Vec::with_capacity(manifest.sheets.len());
+    for sheet in &manifest.sheets {
+        let path = load_context.asset_path().resolve_embed(&sheet.path)?;
+        let image = load_context.loader().immediate().load::<Image>(path).await?;
+        images.push(image.take());
+    }
+
+    let mut builder = TextureAtlasBuilder::default();
+    builder.max_size(UVec2::splat(ATLAS_MAX_SIZE));
+    for image in &images {
+        builder.add_texture(None, image);
+    }
+    let (atlas_layout, _, atlas) = builder.build()?;
+
+    let mut rects = Vec::new();
+    let mut ranges = Vec::with_capacity(manifest.sheets.len());
+    for (sheet, placed) in manifest.sheets.iter().zip(&atlas_layout.textures) {
+        let cells = TextureAtlasLayout::from_grid(
+            sheet.size,
+            sheet.sprites.x,
+            sheet.sprites.y,
+            sheet.padding,
+            sheet.offset,
+        );
+        let first = rects.len();
+        rects.extend(
+            cells
+                .textures
+                .iter()
+                .map(|cell| URect::from_corners(cell.min + placed.min, cell.max + placed.min)),
+        );
+        ranges.push((first, rects.len() - 1));
+    }
+
+    let rects: Arc<[URect]> = rects.into();
+    let frames = ranges
+        .into_iter()
+        .map(|(first, last)| SheetFrames::Rects {
+            image_size: atlas_layout.size,
+            rects: rects.clone(),
+            first,
+            last,
+        })
+        .collect();
+
+    Ok((load_context.add_labeled_asset("atlas".to_string(), atlas), frames))
+}
+
+/// Loads `*.spriteset.ron` manifests into `SpriteSet` assets.
+#[derive(Default)]
+pub struct SpriteSetLoader;
+
+impl AssetLoader for SpriteSetLoader {
+    type Asset = SpriteSet;
+    type Settings = ();
+    type Error = SpriteSetLoaderError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        load_context: &mut LoadContext<'_>,
+    ) -> Result<SpriteSet, SpriteSetLoaderError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let manifest: SpriteSetManifest = manifest_options().from_bytes(&bytes)?;
+        for sheet in &manifest.sheets {
+            let name = || format!("{}_{}", sheet.direction.as_str(), sheet.state.as_str());
+            if let Some(error) = sheet.grid_error() {
+                return Err(SpriteSetLoaderError::Grid(name(), error));
+            }
+            if let Some(error) = sheet.timing_error() {
+                return Err(SpriteSetLoaderError::Timing(name(), error));
+            }
+        }
+
+        // either one shared atlas, or each sheet's own image cut by its grid
+        let (images, frames) = if manifest.pack {
+            let (atlas, frames) = pack_sheets(&manifest, load_context).await?;
+            (vec![atlas; frames.len()], frames)
+        } else {
+            let mut images = Vec::with_capacity(manifest.sheets.len());
+            for sheet in &manifest.sheets {
+                let path = load_context.asset_path().resolve_embed(&sheet.path)?;
+                images.push(load_context.load(path));
+            }
+            (images, vec![SheetFrames::Grid; manifest.sheets.len()])
+        };
+
+        let sheets = manifest
+            .sheets
+            .iter()
+            .zip(images.into_iter().zip(frames))
+            .map(|(sheet, (image, frames))| SpriteSheet {
+                image,
+                grid: sheet.grid(),
+                frames,
+                frame_durations: sheet.frame_durations().into(),
+                playback: sheet.playback,
+                events: sheet.events.clone().into(),
+                flip_x: false,
+                anchor: sheet.anchor.or(manifest.anchor),
+                scale: sheet.scale.unwrap_or(manifest.scale),
+                ground_speed: sheet.ground_speed,
+            })
+            .collect();
+
+        let mut set = SpriteSet {
+            resolution: manifest.directions,
+            sheets,
+            mirrored: Vec::new(),
+            sped_up: Vec::new(),
+        };
+        set.mirror_missing();
+        set.fall_back_running();
+        set.log_report(load_context.path());
+
+        Ok(set)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["spriteset.ron"]
+    }
+}
+
+/// Path of the manifest for a sprite set under `assets/textures`.
+pub fn sprite_set_path(name: &str) -> String {
+    format!("textures/{}/{}.spriteset.ron", name, name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sheet(fields: &str) -> SheetManifest {
+        let ron = format!("(direction: East, state: Moving, path: \"east_moving_4x2_32x32.png\", {})", fields);
+        manifest_options().from_str(&ron).unwrap()
+    }
+
+    #[test]
+    fn grid_needs_frames_and_pixels() {
+        assert_eq!(sheet("sprites: (4, 2), size: (32, 32)").grid_error(), None);
+        for fields in [
+            "sprites: (0, 2), size: (32, 32)",
+            "sprites: (4, 0), size: (32, 32)",
+            "sprites: (4, 2), size: (0, 32)",
+            "sprites: (4, 2), size: (32, 0)",
+        ] {
+            assert!(sheet(fields).grid_error().is_some(), "{}", fields);
+        }
+    }
+
+    #[test]
+    fn timing_must_be_positive_and_finite() {
+        let grid = "sprites: (4, 2), size: (32, 32)";
+        assert_eq!(sheet(grid).timing_error(), None);
+        assert_eq!(sheet(&format!("{}, frame_rate: 12.0, frame_durations: [0.5]", grid)).timing_error(), None);
+
+        for timing in ["frame_time: 0.0", "frame_time: -0.1", "frame_rate: 0.0", "frame_rate: inf", "frame_rate: NaN"] {
+            assert!(sheet(&format!("{}, {}", grid, timing)).timing_error().is_some(), "{}", timing);
+        }
+        let error = sheet(&format!("{}, frame_durations: [0.1, 0.0]", grid)).timing_error();
+        assert_eq!(error.as_deref(), Some("frame_durations[1] 0 is not a positive number"));
+    }
+
+    #[test]
+    fn frame_rate_overrides_frame_time() {
+        let durations = sheet("sprites: (2, 2), size: (8, 8), frame_time: 0.5, frame_rate: 4.0, frame_durations: [1.0]")
+            .frame_durations();
+        assert_eq!(durations, vec![1.0, 0.25, 0.25, 0.25]);
+    }
+}
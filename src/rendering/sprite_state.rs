@@ -1,24 +1,159 @@
 use bevy::prelude::*;
 use enum_iterator::Sequence;
-use crate::rendering::sprite_set::Grid;
-use crate::direction::Direction8;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use crate::rendering::sprite_library::SharedSheet;
+use crate::rendering::sprite_set::{DEFAULT_FRAME_TIME, FrameEvent};
+use crate::direction::Direction16;
 
-#[derive(Component)]
+/// How a clip moves on after its last frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum PlaybackMode {
+    /// Wraps from the last frame back to the first.
+    #[default]
+    Loop,
+    /// Plays through once, then rewinds to the first frame and stops.
+    Once,
+    /// Plays forwards then backwards, a cycle ends back on the first frame.
+    PingPong,
+    /// Plays through once and holds the last frame.
+    Clamp,
+}
+
+#[derive(Component, Default)]
 pub struct AnimationIndices {
     pub first: usize,
     pub last: usize,
     pub current: usize,
+    pub mode: PlaybackMode,
+    /// Ping-pong clips are playing backwards.
+    pub reverse: bool,
+    /// The clip just completed a cycle and has not advanced since.
+    /// One shot clips (`Once`, `Clamp`) stay finished and stop advancing.
+    pub finished: bool,
+}
+
+impl AnimationIndices {
+    /// A one shot clip that has played through and no longer advances.
+    pub fn stopped(&self) -> bool {
+        self.finished && matches!(self.mode, PlaybackMode::Once | PlaybackMode::Clamp)
+    }
+
+    /// Moves `current` to the next frame according to the playback mode.
+    pub fn advance(&mut self) {
+        if self.stopped() {
+            return;
+        }
+        self.finished = false;
+
+        match self.mode {
+            PlaybackMode::Loop | PlaybackMode::Once => {
+                if self.current >= self.last {
+                    self.current = self.first;
+                    self.finished = true;
+                } else {
+                    self.current += 1;
+                }
+            }
+            PlaybackMode::Clamp => {
+                if self.current >= self.last {
+                    self.finished = true;
+                } else {
+                    self.current += 1;
+                }
+            }
+            PlaybackMode::PingPong => {
+                if self.first == self.last {
+                    self.finished = true;
+                } else if self.reverse {
+                    self.current -= 1;
+                    if self.current == self.first {
+                        self.reverse = false;
+                        self.finished = true;
+                    }
+                } else {
+                    self.current += 1;
+                    if self.current == self.last {
+                        self.reverse = true;
+                    }
+                }
+            }
+        }
+    }
 }
 
 #[derive(Component, Deref, DerefMut, Default)]
 pub struct AnimationTimer(pub Timer);
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Component, Sequence)]
+/// Seconds each frame of the shown sheet is held, indexed from `AnimationIndices::first`.
+#[derive(Component, Clone, Default)]
+pub struct FrameDurations(pub Arc<[f32]>);
+
+impl FrameDurations {
+    pub fn get(&self, frame: usize) -> Duration {
+        Duration::from_secs_f32(self.0.get(frame).copied().unwrap_or(DEFAULT_FRAME_TIME))
+    }
+}
+
+/// Ground speed the shown sheet was drawn for, see `SpriteSheet::ground_speed`.
+#[derive(Component, Clone, Copy, Default)]
+pub struct ClipGroundSpeed(pub Option<f32>);
+
+impl ClipGroundSpeed {
+    /// Playback rate that keeps the feet planted when moving at `speed`, 1 for clips without a ground speed.
+    pub fn rate(&self, speed: f32) -> f32 {
+        match self.0 {
+            Some(drawn) if drawn > 0.0 => speed / drawn,
+            _ => 1.0,
+        }
+    }
+}
+
+/// Named events of the shown sheet, see `FrameEvent`.
+#[derive(Component, Clone, Default)]
+pub struct FrameEvents(pub Arc<[FrameEvent]>);
+
+/// Fired when a character's sprite enters a frame that declares an event.
+#[derive(Event, Debug, Clone)]
+pub struct AnimationEvent {
+    pub entity: Entity,
+    pub name: String,
+    pub direction: Direction16,
+    pub state: SpriteState,
+}
+
+impl FrameEvents {
+    /// Sends every event declared on `frame` of the shown sheet.
+    pub fn fire(
+        &self,
+        writer: &mut EventWriter<AnimationEvent>,
+        entity: Entity,
+        shown: ShownSheet,
+        frame: usize,
+    ) {
+        let Some((direction, state)) = shown.0 else {
+            return;
+        };
+
+        for event in self.0.iter().filter(|event| event.frame == frame) {
+            writer.write(AnimationEvent {
+                entity,
+                name: event.name.clone(),
+                direction,
+                state,
+            });
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Component, Sequence, Serialize, Deserialize)]
 pub enum SpriteState {
     Still,
     Starting,
     Moving,
     Stopping,
+    Running,
 }
 
 // to string
@@ -29,52 +164,219 @@ impl SpriteState {
             SpriteState::Starting => "starting",
             SpriteState::Moving => "moving",
             SpriteState::Stopping => "stopping",
+            SpriteState::Running => "running",
         }
     }
 
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(s: &str) -> Option<SpriteState> {
         match s {
             "still" => Some(SpriteState::Still),
             "starting" => Some(SpriteState::Starting),
             "moving" => Some(SpriteState::Moving),
             "stopping" => Some(SpriteState::Stopping),
+            "running" => Some(SpriteState::Running),
             _ => None,
         }
     }
 }
 
-#[derive(Bundle)]
+/// The (facing, state) sheet a character's sprite is showing, `None` until the first one is picked.
+#[derive(Component, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ShownSheet(pub Option<(Direction16, SpriteState)>);
+
+/// The single animated sprite of a character, its image and atlas are swapped
+/// from the sprite set's lookup table whenever the shown sheet changes.
+#[derive(Bundle, Default)]
 pub struct SpriteBundle {
-    pub direction: Direction8,
-    pub state: SpriteState,
     pub sprite: Sprite,
     pub animation_timer: AnimationTimer,
     pub indices: AnimationIndices,
-    pub visibility: Visibility,
-    pub transform: Transform,
+    pub durations: FrameDurations,
+    pub events: FrameEvents,
+    pub clip_speed: ClipGroundSpeed,
+    pub shown: ShownSheet,
 }
 
 impl SpriteBundle {
-    pub fn create(image: Handle<Image>, atlas: Handle<TextureAtlasLayout>, grid: &Grid) -> Self {
-        
-        return SpriteBundle {
-            direction: grid.direction,
-            state: grid.state,
-            sprite: Sprite::from_atlas_image(
-                image,
-                TextureAtlas {
-                    layout: atlas,
-                    index: 0,
+    /// Points the sprite at a sheet. When only the direction changed the current frame is kept,
+    /// so a walk cycle carries on through a turn instead of restarting.
+    #[allow(clippy::too_many_arguments)]
+    pub fn show(
+        sprite: &mut Sprite,
+        indices: &mut AnimationIndices,
+        timer: &mut AnimationTimer,
+        durations: &mut FrameDurations,
+        events: &mut FrameEvents,
+        clip_speed: &mut ClipGroundSpeed,
+        shared: &SharedSheet,
+        keep_frame: bool,
+    ) {
+        let first = shared.sheet.first_frame();
+        let last = shared.sheet.last_frame();
+        let index = if keep_frame {
+            first + (indices.current - indices.first).min(last - first)
+        } else {
+            first
+        };
+
+        sprite.image = shared.sheet.image.clone();
+        sprite.texture_atlas = Some(TextureAtlas {
+            layout: shared.layout.clone(),
+            index,
+        });
+        sprite.custom_size = Some(shared.sheet.world_size());
+        sprite.anchor = shared.sheet.sprite_anchor();
+        sprite.flip_x = shared.sheet.flip_x;
+
+        // a ping-pong clip carries on the way it was heading, and turns back if it lands on the last frame
+        let reverse =
+            keep_frame && shared.sheet.playback == PlaybackMode::PingPong && (indices.reverse || index == last);
+        *indices = AnimationIndices {
+            first,
+            last,
+            current: index,
+            mode: shared.sheet.playback,
+            reverse,
+            finished: false,
+        };
+
+        *durations = FrameDurations(shared.sheet.frame_durations.clone());
+        *events = FrameEvents(shared.sheet.events.clone());
+        *clip_speed = ClipGroundSpeed(shared.sheet.ground_speed);
+
+        if keep_frame {
+            timer.set_duration(durations.get(index - first));
+        } else {
+            *timer = AnimationTimer(Timer::new(durations.get(index - first), TimerMode::Repeating));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rendering::sprite_set::{Grid, SheetFrames, SpriteSheet};
+
+    fn clip(mode: PlaybackMode, frames: usize) -> AnimationIndices {
+        AnimationIndices {
+            last: frames - 1,
+            mode,
+            ..default()
+        }
+    }
+
+    /// Frames shown over `steps` advances, starting with the current one.
+    fn play(indices: &mut AnimationIndices, steps: usize) -> Vec<usize> {
+        let mut shown = vec![indices.current];
+        for _ in 0..steps {
+            indices.advance();
+            shown.push(indices.current);
+        }
+        shown
+    }
+
+    #[test]
+    fn playback_modes() {
+        assert_eq!(play(&mut clip(PlaybackMode::Loop, 3), 4), [0, 1, 2, 0, 1]);
+        assert_eq!(play(&mut clip(PlaybackMode::PingPong, 3), 6), [0, 1, 2, 1, 0, 1, 2]);
+
+        let mut once = clip(PlaybackMode::Once, 3);
+        assert_eq!(play(&mut once, 4), [0, 1, 2, 0, 0]);
+        assert!(once.stopped());
+
+        let mut clamp = clip(PlaybackMode::Clamp, 3);
+        assert_eq!(play(&mut clamp, 4), [0, 1, 2, 2, 2]);
+        assert!(clamp.stopped());
+    }
+
+    #[test]
+    fn finished_marks_the_end_of_each_cycle() {
+        let mut looping = clip(PlaybackMode::Loop, 2);
+        let finished: Vec<bool> = (0..4).map(|_| {
+            looping.advance();
+            looping.finished
+        }).collect();
+        assert_eq!(finished, [false, true, false, true]);
+
+        let mut ping_pong = clip(PlaybackMode::PingPong, 3);
+        let finished: Vec<bool> = (0..4).map(|_| {
+            ping_pong.advance();
+            ping_pong.finished
+        }).collect();
+        assert_eq!(finished, [false, false, false, true]);
+    }
+
+    fn sheet(playback: PlaybackMode, frames: u32) -> SharedSheet {
+        SharedSheet {
+            sheet: SpriteSheet {
+                image: Handle::default(),
+                grid: Grid {
+                    sprites: UVec2::new(frames, 1),
+                    size: UVec2::new(8, 8),
+                    padding: None,
+                    offset: None,
+                    direction: Direction16::East,
+                    state: SpriteState::Moving,
                 },
-            ),
-            animation_timer: AnimationTimer(Timer::from_seconds(0.2, TimerMode::Repeating)),
-            indices: AnimationIndices {
-                first: 0,
-                last: (grid.sprites[0] * grid.sprites[1]) as usize - 1,
-                current: 0,
+                frames: SheetFrames::Grid,
+                frame_durations: vec![0.1; frames as usize].into(),
+                playback,
+                events: Arc::new([]),
+                flip_x: false,
+                anchor: None,
+                scale: 1.0,
+                ground_speed: None,
             },
-            visibility: Visibility::Hidden,
-            transform: Transform::from_scale(Vec3::ONE / 2.0),
-        };
+            layout: Handle::default(),
+        }
+    }
+
+    /// Shows `shared` in place of the sheet `indices` was playing.
+    fn swap(indices: &mut AnimationIndices, shared: &SharedSheet, keep_frame: bool) {
+        SpriteBundle::show(
+            &mut Sprite::default(),
+            indices,
+            &mut AnimationTimer::default(),
+            &mut FrameDurations::default(),
+            &mut FrameEvents::default(),
+            &mut ClipGroundSpeed::default(),
+            shared,
+            keep_frame,
+        );
+    }
+
+    #[test]
+    fn turning_keeps_the_ping_pong_direction() {
+        let shared = sheet(PlaybackMode::PingPong, 4);
+        let mut indices = AnimationIndices::default();
+        swap(&mut indices, &shared, false);
+        // 0 1 2 3 then back to 2
+        play(&mut indices, 4);
+        assert!(indices.reverse);
+
+        swap(&mut indices, &sheet(PlaybackMode::PingPong, 4), true);
+        assert_eq!(play(&mut indices, 3), [2, 1, 0, 1]);
+    }
+
+    #[test]
+    fn turning_onto_a_shorter_ping_pong_turns_back_at_its_end() {
+        let mut indices = AnimationIndices::default();
+        swap(&mut indices, &sheet(PlaybackMode::PingPong, 6), false);
+        play(&mut indices, 4);
+        assert!(!indices.reverse);
+
+        swap(&mut indices, &sheet(PlaybackMode::PingPong, 3), true);
+        assert_eq!(play(&mut indices, 3), [2, 1, 0, 1]);
+    }
+
+    #[test]
+    fn changing_clip_starts_forwards() {
+        let mut indices = AnimationIndices::default();
+        swap(&mut indices, &sheet(PlaybackMode::PingPong, 4), false);
+        play(&mut indices, 4);
+
+        swap(&mut indices, &sheet(PlaybackMode::PingPong, 4), false);
+        assert_eq!(play(&mut indices, 2), [0, 1, 2]);
     }
 }
@@ -0,0 +1,27 @@
+use bevy::prelude::*;
+
+/// World units of ground y per unit of depth, keeps depths well inside the 2d camera's clip range.
+pub const Y_SORT_SCALE: f32 = 1000.0;
+
+/// Draws the entity in front of everything higher up the screen, by deriving its z from its y.
+/// `offset` shifts the y used for sorting, negative values bring the entity forward.
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct YSort {
+    pub offset: f32,
+}
+
+/// Depth of an entity standing on the ground at `y`.
+pub fn y_sort_depth(y: f32, offset: f32) -> f32 {
+    -(y + offset) / Y_SORT_SCALE
+}
+
+pub fn y_sort(mut query: Query<(&mut Transform, &YSort)>) {
+    for (mut transform, sort) in query.iter_mut() {
+        let z = y_sort_depth(transform.translation.y, sort.offset);
+
+        // only write on change, so the transform isn't marked changed every frame
+        if transform.translation.z != z {
+            transform.translation.z = z;
+        }
+    }
+}
@@ -3,17 +3,33 @@ use crate::game::character_state::CharacterState;
 use crate::game::player_input::PlayerControl;
 use crate::game::character_input::RandomInput;
 use crate::game::character_input::CharacterInput;
-use crate::rendering::sprite_set::{get_textures, parse_grid_from_filename};
-use crate::rendering::sprite_state::{SpriteBundle, SpriteState};
+use crate::game::action_buffer::ActionBuffer;
+use crate::game::replay::ReplayId;
+use crate::game::movement::{Acceleration, Facing, GroundSpeed, MoveSpeed, SpeedRamp, Velocity};
+use crate::rendering::animation_graph::{AnimationParams, CharacterAnimation};
+use crate::rendering::sprite_library::SpriteSetLibrary;
+use crate::rendering::sprite_set::SpriteSet;
+use crate::rendering::sprite_state::{ShownSheet, SpriteBundle, SpriteState};
+use crate::rendering::y_sort::YSort;
 use bevy::prelude::*;
 
 #[derive(Bundle)]
 pub struct CharacterBundle {
     pub direction: Direction8,
+    pub facing: Facing,
     pub transform: Transform,
     pub character_state: CharacterState,
     pub character_input: CharacterInput,
+    pub action_buffer: ActionBuffer,
+    pub move_speed: MoveSpeed,
+    pub acceleration: Acceleration,
+    pub speed_ramp: SpeedRamp,
+    pub velocity: Velocity,
+    pub ground_speed: GroundSpeed,
     pub sprite_state: SpriteState,
+    pub sprite: SpriteBundle,
+    pub animation_params: AnimationParams,
+    pub y_sort: YSort,
     pub visibility: Visibility,
 }
 
@@ -21,90 +37,161 @@ impl Default for CharacterBundle {
     fn default() -> Self {
         CharacterBundle {
             direction: Direction8::East,
+            facing: Facing(Direction8::East.to_translation()),
             transform: Transform::default(),
             character_state: CharacterState::Still,
             character_input: CharacterInput::default(),
+            action_buffer: ActionBuffer::default(),
+            move_speed: MoveSpeed::default(),
+            acceleration: Acceleration::default(),
+            speed_ramp: SpeedRamp::default(),
+            velocity: Velocity::default(),
+            ground_speed: GroundSpeed::default(),
             sprite_state: SpriteState::Still,
+            sprite: SpriteBundle::default(),
+            animation_params: AnimationParams::default(),
+            y_sort: YSort::default(),
             visibility: Visibility::Hidden,
         }
     }
 }
 
-pub fn make_children(
-    files: Vec<String>,
-    asset_server: &Res<AssetServer>,
-    texture_atlas_layouts: &mut ResMut<Assets<TextureAtlasLayout>>,
-) -> Vec<SpriteBundle> {
-    let mut sprites = Vec::new();
-
-    for filename in files {
-        if let Some(grid) = parse_grid_from_filename(&filename) {
-            let texture = asset_server.load(&filename);
-            let layout = TextureAtlasLayout::from_grid(
-                grid.size,
-                grid.sprites[0],
-                grid.sprites[1],
-                None,
-                None,
-            );
-            let texture_atlas_layout = texture_atlas_layouts.add(layout);
-
-            let sprite_bundle = SpriteBundle::create(texture, texture_atlas_layout, &grid);
-            sprites.push(sprite_bundle);
+/// Walk and run speeds of the player.
+pub const PLAYER_SPEED: MoveSpeed = MoveSpeed { walk: 10.0, run: 20.0 };
+
+/// Walk and run speeds of the wandering test characters, a little slower than the player.
+pub const NPC_SPEED: MoveSpeed = MoveSpeed { walk: 8.0, run: 16.0 };
+
+/// Animation graph shared by the test characters.
+pub const CHARACTER_GRAPH: &str = "animations/character.animgraph.ron";
+
+/// Name of the sprite set a character is drawn with.
+#[derive(Component)]
+pub struct CharacterSprites(pub String);
+
+/// Takes the character's acceleration ramps from the length of its Starting and Stopping clips,
+/// so the body speeds up and slows down over exactly the frames drawn for it.
+#[derive(Component)]
+pub struct RampsFromClips;
+
+/// Marks characters whose sprite set has not finished loading yet.
+#[derive(Component)]
+pub struct PendingSprites;
+
+/// Builds the lookup table of each character's sprite set once it has loaded.
+pub fn attach_character_sprites(
+    mut commands: Commands,
+    mut library: ResMut<SpriteSetLibrary>,
+    sprite_sets: Res<Assets<SpriteSet>>,
+    mut texture_atlas_layouts: ResMut<Assets<TextureAtlasLayout>>,
+    query: Query<(Entity, &CharacterSprites), With<PendingSprites>>,
+) {
+    for (character, sprites) in query.iter() {
+        if library.table(&sprites.0, &sprite_sets, &mut texture_atlas_layouts).is_some() {
+            commands.entity(character).remove::<PendingSprites>();
         }
     }
+}
+
+/// Rebuilds the table of a sprite set that was reloaded from disk, then has every character
+/// drawn with it pick its sheet again so new frames, grids and timings show straight away.
+/// Changed images are reloaded in place by Bevy, a changed manifest or export comes through here.
+pub fn reload_character_sprites(
+    mut asset_events: EventReader<AssetEvent<SpriteSet>>,
+    mut library: ResMut<SpriteSetLibrary>,
+    sprite_sets: Res<Assets<SpriteSet>>,
+    mut texture_atlas_layouts: ResMut<Assets<TextureAtlasLayout>>,
+    mut query: Query<(&CharacterSprites, &mut ShownSheet)>,
+) {
+    for event in asset_events.read() {
+        let AssetEvent::Modified { id } = event else {
+            continue;
+        };
 
-    sprites
+        for name in library.invalidate(*id) {
+            library.table(&name, &sprite_sets, &mut texture_atlas_layouts);
+            info!("reloaded sprite set {}", name);
+
+            for (sprites, mut shown) in query.iter_mut() {
+                if sprites.0 == name {
+                    shown.0 = None;
+                }
+            }
+        }
+    }
+}
+
+/// Keeps the acceleration of `RampsFromClips` characters in step with their sprite set,
+/// including after it is reloaded.
+pub fn match_ramps_to_clips(
+    library: Res<SpriteSetLibrary>,
+    mut query: Query<(&CharacterSprites, &mut Acceleration), With<RampsFromClips>>,
+) {
+    for (sprites, mut acceleration) in query.iter_mut() {
+        let Some(table) = library.get(&sprites.0) else {
+            continue;
+        };
+
+        let ramps = Acceleration {
+            start: table.clip_duration(SpriteState::Starting).unwrap_or(acceleration.start),
+            stop: table.clip_duration(SpriteState::Stopping).unwrap_or(acceleration.stop),
+        };
+        if ramps.start != acceleration.start || ramps.stop != acceleration.stop {
+            *acceleration = ramps;
+        }
+    }
 }
 
 pub fn spawn_player(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
-    mut texture_atlas_layouts: ResMut<Assets<TextureAtlasLayout>>,
+    mut library: ResMut<SpriteSetLibrary>,
 ) {
-    let filenames = get_textures("test_char");
-    let children = make_children(filenames, &asset_server, &mut texture_atlas_layouts);
-
-    let parent = commands
-        .spawn((
-            CharacterBundle::default(),
-            PlayerControl::default(),
-        )).id();
-
-    for child in children {
-        commands.entity(parent).with_children(|parent| {
-            parent.spawn(child);
-        });
-    }
+    library.load("test_char", &asset_server);
+
+    commands.spawn((
+        CharacterBundle {
+            move_speed: PLAYER_SPEED,
+            ..default()
+        },
+        CharacterSprites("test_char".to_string()),
+        CharacterAnimation(asset_server.load(CHARACTER_GRAPH)),
+        PendingSprites,
+        RampsFromClips,
+        PlayerControl::default(),
+        ReplayId(0),
+    ));
 }
 
 pub fn spawn_character(
     commands: &mut Commands,
     asset_server: &Res<AssetServer>,
-    texture_atlas_layouts: &mut ResMut<Assets<TextureAtlasLayout>>,
+    library: &mut ResMut<SpriteSetLibrary>,
     transform: Transform,
+    id: ReplayId,
 ) {
-    let filenames = get_textures("test_char");
-    let children = make_children(filenames, &asset_server, texture_atlas_layouts);
+    library.load("test_char", asset_server);
 
     let parent = commands.spawn((
-        CharacterBundle::default(),
-        RandomInput::default()
+        CharacterBundle {
+            move_speed: NPC_SPEED,
+            ..default()
+        },
+        CharacterSprites("test_char".to_string()),
+        CharacterAnimation(asset_server.load(CHARACTER_GRAPH)),
+        PendingSprites,
+        RampsFromClips,
+        RandomInput::default(),
+        id,
     )).id();
 
-    for child in children {
-        commands.entity(parent).with_children(|parent| {
-            parent.spawn(child);
-        });
-    }
-
     commands.entity(parent).insert(transform);
 }
 
 pub fn spawn_characters(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
-    mut texture_atlas_layouts: ResMut<Assets<TextureAtlasLayout>>,
+    mut library: ResMut<SpriteSetLibrary>,
 ) {
     let transforms = vec![
         Transform::from_translation(Vec3::new(-300.0, 0.0, 0.0)),
@@ -117,7 +204,9 @@ pub fn spawn_characters(
         Transform::from_translation(Vec3::new(300.0, -300.0, 0.0)),
     ];
 
-    for transform in transforms {
-        spawn_character(&mut commands, &asset_server, &mut texture_atlas_layouts, transform);
+    // the player is replay id 0
+    for (index, transform) in transforms.into_iter().enumerate() {
+        let id = ReplayId(index as u32 + 1);
+        spawn_character(&mut commands, &asset_server, &mut library, transform, id);
     }
 }
@@ -0,0 +1,593 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use bevy::math::UVec2;
+use enum_iterator::all;
+use crate::direction::{Direction16, DirectionResolution};
+use crate::pack::parse_sheet_file_name;
+use crate::rendering::aseprite::AsepriteExport;
+use crate::rendering::sprite_set::{SheetManifest, SpriteSetManifest, manifest_options};
+use crate::rendering::sprite_state::SpriteState;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The set will not show correctly in game.
+    Error,
+    /// Probably a mistake, but the set still works.
+    Warning,
+}
+
+#[derive(Debug)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+}
+
+/// Everything found wrong with one sprite set folder.
+#[derive(Debug)]
+pub struct SetReport {
+    pub dir: PathBuf,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl SetReport {
+    fn error(&mut self, message: String) {
+        self.diagnostics.push(Diagnostic { severity: Severity::Error, message });
+    }
+
+    fn warning(&mut self, message: String) {
+        self.diagnostics.push(Diagnostic { severity: Severity::Warning, message });
+    }
+
+    fn count(&self, severity: Severity) -> usize {
+        self.diagnostics.iter().filter(|d| d.severity == severity).count()
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.count(Severity::Error) > 0
+    }
+}
+
+impl fmt::Display for SetReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let errors = self.count(Severity::Error);
+        let warnings = self.count(Severity::Warning);
+        if errors == 0 && warnings == 0 {
+            return write!(f, "{}: ok", self.dir.display());
+        }
+
+        write!(f, "{}: {} error(s), {} warning(s)", self.dir.display(), errors, warnings)?;
+        for diagnostic in &self.diagnostics {
+            let label = match diagnostic.severity {
+                Severity::Error => "error",
+                Severity::Warning => "warning",
+            };
+            write!(f, "\n  {}: {}", label, diagnostic.message)?;
+        }
+        Ok(())
+    }
+}
+
+/// Pixels a grid of frames covers, laid out the way `TextureAtlasLayout::from_grid` cuts it.
+fn grid_extent(sheet: &SheetManifest) -> UVec2 {
+    let padding = sheet.padding.unwrap_or(UVec2::ZERO);
+    let offset = sheet.offset.unwrap_or(UVec2::ZERO);
+    offset + sheet.sprites * sheet.size + (sheet.sprites.saturating_sub(UVec2::ONE)) * padding
+}
+
+fn sheet_name(sheet: &SheetManifest) -> String {
+    format!("{}_{}", sheet.direction.as_str(), sheet.state.as_str())
+}
+
+/// Checks the sheet's image against its manifest entry and its file name.
+fn validate_sheet(dir: &Path, sheet: &SheetManifest, report: &mut SetReport) {
+    let name = sheet_name(sheet);
+    let frames = (sheet.sprites.x * sheet.sprites.y) as usize;
+
+    if let Some(error) = sheet.grid_error() {
+        report.error(format!("{}: {}", name, error));
+        return;
+    }
+
+    match parse_sheet_file_name(&sheet.path) {
+        None => report.warning(format!(
+            "{}: {} is not named <direction>_<state>_CxR_WxH.png",
+            name, sheet.path
+        )),
+        Some((prefix, sprites, size)) => {
+            if prefix != name {
+                report.error(format!("{}: {} is named for {}", name, sheet.path, prefix));
+            }
+            if sprites != sheet.sprites {
+                report.error(format!(
+                    "{}: {} is named as a {}x{} grid, the manifest says {}x{}",
+                    name, sheet.path, sprites.x, sprites.y, sheet.sprites.x, sheet.sprites.y
+                ));
+            }
+            if size != sheet.size {
+                report.error(format!(
+                    "{}: {} is named for {}x{} frames, the manifest says {}x{}",
+                    name, sheet.path, size.x, size.y, sheet.size.x, sheet.size.y
+                ));
+            }
+        }
+    }
+
+    match image::image_dimensions(dir.join(&sheet.path)) {
+        Err(e) => report.error(format!("{}: could not read {}: {}", name, sheet.path, e)),
+        Ok(dimensions) => {
+            let dimensions = UVec2::from(dimensions);
+            let extent = grid_extent(sheet);
+            // trailing padding after the last row or column is harmless
+            let trailing = extent + sheet.padding.unwrap_or(UVec2::ZERO);
+            if dimensions != extent && dimensions != trailing {
+                report.error(format!(
+                    "{}: {} is {}x{}, a {}x{} grid of {}x{} frames needs {}x{}",
+                    name,
+                    sheet.path,
+                    dimensions.x,
+                    dimensions.y,
+                    sheet.sprites.x,
+                    sheet.sprites.y,
+                    sheet.size.x,
+                    sheet.size.y,
+                    extent.x,
+                    extent.y
+                ));
+            }
+        }
+    }
+
+    if let Some(error) = sheet.timing_error() {
+        report.error(format!("{}: {}", name, error));
+    }
+    if sheet.frame_durations.len() > frames {
+        report.warning(format!(
+            "{}: {} frame durations for {} frames, the extra ones are ignored",
+            name,
+            sheet.frame_durations.len(),
+            frames
+        ));
+    }
+    for event in &sheet.events {
+        if event.frame >= frames {
+            report.error(format!(
+                "{}: event {} is on frame {}, the sheet has {} frames",
+                name, event.name, event.frame, frames
+            ));
+        }
+    }
+}
+
+/// Checks that every (facing, state) of the set's resolution has a sheet,
+/// either its own or one mirrored from the opposite side.
+fn validate_coverage(directions: DirectionResolution, sheets: &[(Direction16, SpriteState)], report: &mut SetReport) {
+    let mut seen: HashMap<(Direction16, SpriteState), usize> = HashMap::new();
+    for &(direction, state) in sheets {
+        *seen.entry((direction, state)).or_default() += 1;
+
+        if !directions.contains(direction) {
+            report.warning(format!(
+                "{}_{}: not one of the set's {} directions, it is never shown",
+                direction.as_str(),
+                state.as_str(),
+                directions.count()
+            ));
+        }
+    }
+
+    for ((direction, state), count) in &seen {
+        if *count > 1 {
+            report.error(format!(
+                "{}_{}: {} sheets, only the first is used",
+                direction.as_str(),
+                state.as_str(),
+                count
+            ));
+        }
+    }
+
+    let drawn = |direction: Direction16, state| {
+        seen.contains_key(&(direction, state)) || seen.contains_key(&(direction.mirrored(), state))
+    };
+    for direction in directions.facings() {
+        for state in all::<SpriteState>() {
+            // running falls back to the moving clip
+            let covered = drawn(direction, state)
+                || (state == SpriteState::Running && drawn(direction, SpriteState::Moving));
+            if !covered {
+                report.error(format!(
+                    "{}_{}: missing, and {}_{} is missing too so it can't be mirrored",
+                    direction.as_str(),
+                    state.as_str(),
+                    direction.mirrored().as_str(),
+                    state.as_str()
+                ));
+            }
+        }
+    }
+}
+
+/// Checks a `*.spriteset.ron` manifest and its sheets, returning the files it uses.
+fn validate_manifest(dir: &Path, manifest_name: &str, report: &mut SetReport) -> Option<BTreeSet<String>> {
+    let manifest: SpriteSetManifest = match std::fs::read(dir.join(manifest_name)) {
+        Err(e) => {
+            report.error(format!("{}: {}", manifest_name, e));
+            return None;
+        }
+        Ok(bytes) => match manifest_options().from_bytes(&bytes) {
+            Err(e) => {
+                report.error(format!("{}: {}", manifest_name, e));
+                return None;
+            }
+            Ok(manifest) => manifest,
+        },
+    };
+
+    for sheet in &manifest.sheets {
+        validate_sheet(dir, sheet, report);
+    }
+    let keys: Vec<(Direction16, SpriteState)> = manifest.sheets.iter().map(|s| (s.direction, s.state)).collect();
+    validate_coverage(manifest.directions, &keys, report);
+
+    let sizes: BTreeSet<(u32, u32)> = manifest.sheets.iter().map(|s| (s.size.x, s.size.y)).collect();
+    if sizes.len() > 1 {
+        let sizes: Vec<String> = sizes.iter().map(|(w, h)| format!("{}x{}", w, h)).collect();
+        let message = format!("frame sizes differ between sheets: {}", sizes.join(", "));
+        if manifest.mixed_sizes {
+            report.warning(message);
+        } else {
+            report.error(format!("{}, set mixed_sizes if that is meant", message));
+        }
+    }
+
+    let mut used: BTreeSet<String> = manifest.sheets.into_iter().map(|s| s.path).collect();
+    used.insert(manifest_name.to_string());
+    Some(used)
+}
+
+/// Checks an Aseprite `*.aseprite.json` export and its image, returning the files it uses.
+fn validate_aseprite(dir: &Path, export_name: &str, report: &mut SetReport) -> Option<BTreeSet<String>> {
+    let parsed = std::fs::read(dir.join(export_name))
+        .map_err(|e| e.to_string())
+        .and_then(|bytes| serde_json::from_slice::<AsepriteExport>(&bytes).map_err(|e| e.to_string()))
+        .and_then(|export| {
+            let meta = export.meta;
+            export.frames.into_vec().map(|frames| (meta, frames)).map_err(|e| e.to_string())
+        });
+    let (meta, frames) = match parsed {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            report.error(format!("{}: {}", export_name, e));
+            return None;
+        }
+    };
+
+    if let Some(index) = frames.iter().position(|f| f.trimmed) {
+        report.error(format!(
+            "{}: frame {} is trimmed, export without trimming so frames keep their size",
+            export_name, index
+        ));
+    }
+
+    let size = UVec2::new(meta.size.w, meta.size.h);
+    match image::image_dimensions(dir.join(&meta.image)) {
+        Err(e) => report.error(format!("{}: could not read {}: {}", export_name, meta.image, e)),
+        Ok(dimensions) if UVec2::from(dimensions) != size => report.error(format!(
+            "{}: {} is {}x{}, the export says {}x{}",
+            export_name, meta.image, dimensions.0, dimensions.1, size.x, size.y
+        )),
+        Ok(_) => {}
+    }
+    for (index, f) in frames.iter().enumerate() {
+        if f.frame.x + f.frame.w > size.x || f.frame.y + f.frame.h > size.y {
+            report.error(format!(
+                "{}: frame {} reaches past the {}x{} image",
+                export_name, index, size.x, size.y
+            ));
+        }
+    }
+
+    let mut keys = Vec::new();
+    for tag in &meta.frame_tags {
+        let Some(key) = tag.sheet_key() else {
+            report.warning(format!(
+                "{}: tag {} is not <direction>_<state>, it is never shown",
+                export_name, tag.name
+            ));
+            continue;
+        };
+        if tag.from > tag.to || tag.to >= frames.len() {
+            report.error(format!(
+                "{}: tag {} covers frames {}..={}, the export has {} frames",
+                export_name,
+                tag.name,
+                tag.from,
+                tag.to,
+                frames.len()
+            ));
+            continue;
+        }
+        keys.push(key);
+    }
+    let directions = DirectionResolution::smallest_containing(keys.iter().map(|(direction, _)| *direction));
+    validate_coverage(directions, &keys, report);
+
+    Some(BTreeSet::from([export_name.to_string(), meta.image]))
+}
+
+/// Validates the sprite set in `dir`, described by "<dir name>.spriteset.ron",
+/// or by an Aseprite export "<dir name>.aseprite.json" when there is no manifest.
+pub fn validate_set(dir: &Path) -> SetReport {
+    let mut report = SetReport {
+        dir: dir.to_path_buf(),
+        diagnostics: Vec::new(),
+    };
+
+    let Some(name) = dir.file_name().and_then(|n| n.to_str()) else {
+        report.error("could not tell the set name from the folder".to_string());
+        return report;
+    };
+    let manifest_name = format!("{}.spriteset.ron", name);
+    let export_name = format!("{}.aseprite.json", name);
+
+    let aseprite = !dir.join(&manifest_name).exists() && dir.join(&export_name).exists();
+    let (source, used) = if aseprite {
+        let used = validate_aseprite(dir, &export_name, &mut report);
+        (export_name, used)
+    } else {
+        let used = validate_manifest(dir, &manifest_name, &mut report);
+        (manifest_name, used)
+    };
+    let Some(used) = used else {
+        return report;
+    };
+
+    // anything the set doesn't point at
+    match std::fs::read_dir(dir) {
+        Err(e) => report.error(format!("{}: {}", dir.display(), e)),
+        Ok(entries) => {
+            let mut stray: Vec<String> = entries
+                .flatten()
+                .filter_map(|entry| entry.file_name().to_str().map(String::from))
+                .filter(|file| !used.contains(file))
+                .collect();
+            stray.sort();
+            for file in stray {
+                report.warning(format!("{}: not referenced by {}", file, source));
+            }
+        }
+    }
+
+    report
+}
+
+/// Validates every sprite set folder directly under `textures_dir`.
+pub fn validate_all(textures_dir: &Path) -> std::io::Result<Vec<SetReport>> {
+    let mut dirs: Vec<PathBuf> = std::fs::read_dir(textures_dir)?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .collect();
+    dirs.sort();
+
+    Ok(dirs.iter().map(|dir| validate_set(dir)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::RgbaImage;
+
+    const FACINGS: [&str; 3] = ["north", "east", "south"];
+    const STATES: [&str; 4] = ["still", "starting", "moving", "stopping"];
+
+    /// An empty "hero" set folder under the system temp dir, unique to the test.
+    fn set_dir(test: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("px-validate-{}-{}", test, std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let dir = root.join("hero");
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn remove(dir: &Path) {
+        std::fs::remove_dir_all(dir.parent().unwrap()).unwrap();
+    }
+
+    /// Writes a 2x1 sheet of 8x8 frames, and returns its manifest entry.
+    fn sheet(dir: &Path, direction: &str, state: &str) -> String {
+        let path = format!("{}_{}_2x1_8x8.png", direction, state);
+        RgbaImage::new(16, 8).save(dir.join(&path)).unwrap();
+        format!(
+            "(direction: {}, state: {}, path: \"{}\", sprites: (2, 1), size: (8, 8))",
+            capitalize(direction),
+            capitalize(state),
+            path
+        )
+    }
+
+    fn capitalize(name: &str) -> String {
+        name[..1].to_uppercase() + &name[1..]
+    }
+
+    /// Writes a 4 direction set drawing north, east and south, west is mirrored from east.
+    fn complete_set(dir: &Path) -> Vec<String> {
+        FACINGS
+            .iter()
+            .flat_map(|direction| STATES.iter().map(move |state| (direction, state)))
+            .map(|(direction, state)| sheet(dir, direction, state))
+            .collect()
+    }
+
+    fn write_manifest(dir: &Path, sheets: &[String]) {
+        let ron = format!("(directions: Four, sheets: [{}])", sheets.join(", "));
+        std::fs::write(dir.join("hero.spriteset.ron"), ron).unwrap();
+    }
+
+    fn messages(report: &SetReport, severity: Severity) -> Vec<&str> {
+        report
+            .diagnostics
+            .iter()
+            .filter(|d| d.severity == severity)
+            .map(|d| d.message.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn complete_set_is_ok() {
+        let dir = set_dir("complete");
+        write_manifest(&dir, &complete_set(&dir));
+
+        let report = validate_set(&dir);
+        assert!(report.diagnostics.is_empty(), "{}", report);
+
+        remove(&dir);
+    }
+
+    #[test]
+    fn missing_manifest_is_an_error() {
+        let dir = set_dir("no-manifest");
+
+        let report = validate_set(&dir);
+        assert_eq!(messages(&report, Severity::Error).len(), 1);
+        assert!(messages(&report, Severity::Error)[0].starts_with("hero.spriteset.ron: "));
+
+        remove(&dir);
+    }
+
+    #[test]
+    fn coverage_rules() {
+        let dir = set_dir("coverage");
+        let mut sheets = complete_set(&dir);
+        // north_moving is gone and can't be mirrored, east_still is listed twice
+        sheets.retain(|sheet| !sheet.contains("north_moving"));
+        std::fs::remove_file(dir.join("north_moving_2x1_8x8.png")).unwrap();
+        let east_still = sheets.iter().find(|sheet| sheet.contains("east_still")).unwrap().clone();
+        sheets.push(east_still);
+        // a northeast sheet is outside the 4 directions
+        sheets.push(sheet(&dir, "northeast", "still"));
+        write_manifest(&dir, &sheets);
+
+        let report = validate_set(&dir);
+        assert_eq!(
+            messages(&report, Severity::Error),
+            [
+                "east_still: 2 sheets, only the first is used",
+                "north_moving: missing, and north_moving is missing too so it can't be mirrored",
+                // running falls back to the missing moving clip
+                "north_running: missing, and north_running is missing too so it can't be mirrored",
+            ]
+        );
+        assert_eq!(
+            messages(&report, Severity::Warning),
+            ["northeast_still: not one of the set's 4 directions, it is never shown"]
+        );
+
+        remove(&dir);
+    }
+
+    #[test]
+    fn sheets_must_match_their_images() {
+        let dir = set_dir("sheets");
+        let mut sheets = complete_set(&dir);
+        // the image is a frame short of its grid
+        RgbaImage::new(8, 8).save(dir.join("east_still_2x1_8x8.png")).unwrap();
+        // named for another state
+        sheets[0] = sheets[0].replace("state: Still", "state: Starting");
+        sheets[1] = sheets[1].replace("state: Starting", "state: Still");
+        sheets.push(
+            "(direction: South, state: Running, path: \"south_running.png\", sprites: (0, 1), size: (8, 8))".into(),
+        );
+        sheets[2] = sheets[2].replace("size: (8, 8))", "size: (8, 8), frame_time: 0.0)");
+        write_manifest(&dir, &sheets);
+        std::fs::write(dir.join("notes.txt"), "").unwrap();
+
+        let report = validate_set(&dir);
+        let errors = messages(&report, Severity::Error);
+        let needs = "east_still: east_still_2x1_8x8.png is 8x8, a 2x1 grid of 8x8 frames needs 16x8";
+        assert!(errors.contains(&needs), "{}", report);
+        assert!(errors.contains(&"north_starting: north_still_2x1_8x8.png is named for north_still"), "{}", report);
+        assert!(errors.contains(&"south_running: empty grid 0x1 of 8x8 frames"), "{}", report);
+        assert!(errors.contains(&"north_moving: frame_time 0 is not a positive number"), "{}", report);
+        assert_eq!(messages(&report, Severity::Warning), ["notes.txt: not referenced by hero.spriteset.ron"]);
+
+        remove(&dir);
+    }
+
+    #[test]
+    fn frame_sizes_must_match_unless_mixed() {
+        let dir = set_dir("sizes");
+        let mut sheets = complete_set(&dir);
+        RgbaImage::new(32, 16).save(dir.join("east_moving_2x1_16x16.png")).unwrap();
+        std::fs::remove_file(dir.join("east_moving_2x1_8x8.png")).unwrap();
+        for sheet in &mut sheets {
+            if sheet.contains("east_moving") {
+                *sheet = sheet.replace("8x8", "16x16").replace("size: (8, 8)", "size: (16, 16)");
+            }
+        }
+        write_manifest(&dir, &sheets);
+
+        let report = validate_set(&dir);
+        assert_eq!(
+            messages(&report, Severity::Error),
+            ["frame sizes differ between sheets: 8x8, 16x16, set mixed_sizes if that is meant"]
+        );
+
+        let ron = format!("(directions: Four, mixed_sizes: true, sheets: [{}])", sheets.join(", "));
+        std::fs::write(dir.join("hero.spriteset.ron"), ron).unwrap();
+        let report = validate_set(&dir);
+        assert!(messages(&report, Severity::Error).is_empty(), "{}", report);
+        assert_eq!(messages(&report, Severity::Warning), ["frame sizes differ between sheets: 8x8, 16x16"]);
+
+        remove(&dir);
+    }
+
+    /// Writes an Aseprite export with one 8x8 frame per tag, tagged like `complete_set`'s sheets.
+    fn write_export(dir: &Path, trimmed: bool) {
+        let tags: Vec<String> = FACINGS
+            .iter()
+            .flat_map(|direction| STATES.iter().map(move |state| format!("{}_{}", direction, state)))
+            .collect();
+        let frames: Vec<String> = (0..tags.len())
+            .map(|n| {
+                format!(
+                    r#"{{"frame": {{"x": {}, "y": 0, "w": 8, "h": 8}}, "trimmed": {}, "sourceSize": {{"w": 8, "h": 8}}, "duration": 100}}"#,
+                    n * 8,
+                    trimmed && n == 2
+                )
+            })
+            .collect();
+        let tags: Vec<String> = tags
+            .iter()
+            .enumerate()
+            .map(|(n, name)| format!(r#"{{"name": "{}", "from": {}, "to": {}}}"#, name, n, n))
+            .collect();
+        let json = format!(
+            r#"{{"frames": [{}], "meta": {{"image": "hero.png", "size": {{"w": {}, "h": 8}}, "frameTags": [{}]}}}}"#,
+            frames.join(", "),
+            frames.len() * 8,
+            tags.join(", ")
+        );
+        std::fs::write(dir.join("hero.aseprite.json"), json).unwrap();
+        RgbaImage::new(frames.len() as u32 * 8, 8).save(dir.join("hero.png")).unwrap();
+    }
+
+    #[test]
+    fn aseprite_exports_are_validated() {
+        let dir = set_dir("aseprite");
+        write_export(&dir, false);
+
+        let report = validate_set(&dir);
+        assert!(report.diagnostics.is_empty(), "{}", report);
+
+        write_export(&dir, true);
+        let report = validate_set(&dir);
+        assert_eq!(
+            messages(&report, Severity::Error),
+            ["hero.aseprite.json: frame 2 is trimmed, export without trimming so frames keep their size"]
+        );
+
+        remove(&dir);
+    }
+}